@@ -0,0 +1,51 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::Parser;
+use pixelflut_filler::{canvas::Region, mockserver::MockServer};
+
+#[derive(Parser)]
+#[clap(
+    name = "mock-server",
+    about = "A small pixelflut server for testing clients"
+)]
+struct Opt {
+    /// The address to listen on
+    #[clap(short, long, default_value = "127.0.0.1:1337")]
+    listen: String,
+
+    /// The size of the canvas, as `WIDTHxHEIGHT`
+    #[clap(short, long, default_value = "800x600")]
+    size: Region,
+
+    /// Save the canvas to this PNG file periodically
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// How often to save the canvas, in milliseconds
+    #[clap(long, default_value = "1000")]
+    save_interval: u64,
+}
+
+fn main() -> std::io::Result<()> {
+    pretty_env_logger::init();
+
+    let opt = Opt::parse();
+
+    let server = MockServer::start(opt.listen.as_str(), opt.size.width, opt.size.height)?;
+    log::info!(
+        "Listening on {} with a {}x{} canvas",
+        server.address(),
+        opt.size.width,
+        opt.size.height
+    );
+
+    loop {
+        std::thread::sleep(Duration::from_millis(opt.save_interval));
+
+        if let Some(output) = &opt.output {
+            if let Err(e) = server.save_png(output) {
+                log::warn!("Could not save {}: {}", output.display(), e);
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::str::FromStr;
+
+use crate::{color::Color, window::Window};
+
+/// A rectangular part of the canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    /// The region that covers all of `window`
+    pub fn full(window: &Window) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: window.get_x(),
+            height: window.get_y(),
+        }
+    }
+
+    /// The part of this region that lies inside of `window`
+    pub fn clamp(&self, window: &Window) -> Self {
+        let x = self.x.min(window.get_x());
+        let y = self.y.min(window.get_y());
+        Self {
+            x,
+            y,
+            width: self.width.min(window.get_x() - x),
+            height: self.height.min(window.get_y() - y),
+        }
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// All coordinates in this region, row by row
+    pub fn coordinates(&self) -> impl Iterator<Item = (usize, usize)> {
+        let Region {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+        (y..y + height).flat_map(move |y| (x..x + width).map(move |x| (x, y)))
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    /// Parse `WIDTHxHEIGHT` or `WIDTHxHEIGHT+X+Y`, e.g. `100x50+10+20`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid region {}, expected WIDTHxHEIGHT+X+Y", s);
+        let parse = |value: &str| value.parse::<usize>().map_err(|_| invalid());
+
+        let mut parts = s.split('+');
+        let (width, height) = parts
+            .next()
+            .and_then(|size| size.split_once('x'))
+            .ok_or_else(invalid)?;
+
+        let (x, y) = match (parts.next(), parts.next(), parts.next()) {
+            (None, None, None) => (0, 0),
+            (Some(x), Some(y), None) => (parse(x)?, parse(y)?),
+            _ => return Err(invalid()),
+        };
+
+        Ok(Self {
+            x,
+            y,
+            width: parse(width)?,
+            height: parse(height)?,
+        })
+    }
+}
+
+/// The colors of the pixels in a [`Region`], as read back from the server
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    region: Region,
+    pixels: Vec<Option<Color>>,
+}
+
+impl Canvas {
+    pub fn new(region: Region) -> Self {
+        Self {
+            region,
+            pixels: vec![None; region.width * region.height],
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if self.region.contains(x, y) {
+            Some((y - self.region.y) * self.region.width + (x - self.region.x))
+        } else {
+            None
+        }
+    }
+
+    /// The color of the pixel at `x`, `y` (in canvas coordinates), if it
+    /// is inside of this canvas and has been read
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        self.index(x, y).and_then(|index| self.pixels[index])
+    }
+
+    /// Set the color of the pixel at `x`, `y`. Returns `false` if the pixel
+    /// is outside of this canvas.
+    pub fn set(&mut self, x: usize, y: usize, color: Color) -> bool {
+        match self.index(x, y) {
+            Some(index) => {
+                self.pixels[index] = Some(color);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,201 @@
+/// The commands that a server supports, as far as we can tell from its
+/// reply to `HELP`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    /// The binary `PB` command
+    pub binary_px: bool,
+    /// The `COMPRESS` command
+    pub compression: bool,
+    /// The `OFFSET` command
+    pub offset: bool,
+    /// Reading pixels back with `PX x y`
+    pub read_back: bool,
+    /// Reading pixels back with a binary `PB x y` query
+    pub binary_read_back: bool,
+}
+
+impl Capabilities {
+    /// The capabilities of a server that didn't tell us what it supports.
+    /// We assume that it supports everything, and leave it to the user to
+    /// only enable what it supports. The exception is binary read-back,
+    /// which can't be told apart from drawing a pixel by servers that don't
+    /// support it, and is only used if the server mentions it.
+    pub fn unknown() -> Self {
+        Self {
+            binary_px: true,
+            compression: true,
+            offset: true,
+            read_back: true,
+            binary_read_back: false,
+        }
+    }
+
+    /// Guess the capabilities of a server from the lines it replied to
+    /// `HELP` with. If the reply doesn't mention `PX`, it is not a help text
+    /// we understand, and the server is assumed to support everything.
+    pub fn from_help(lines: &[String]) -> Self {
+        let lines: Vec<Vec<String>> = lines.iter().map(|line| words(line)).collect();
+        let mentions = |word: &str| lines.iter().any(|words| words.iter().any(|w| w == word));
+
+        if !mentions("PX") {
+            log::debug!("Unrecognized reply to HELP, assuming that everything is supported");
+            return Self::unknown();
+        }
+
+        // Servers list the query form of a command as `PX x y`, or describe
+        // it as getting or reading a pixel
+        let describes_query = |command: &str| {
+            lines.iter().any(|words| {
+                // The words before the description, if there is one
+                let syntax_len = words.iter().take_while(|w| *w != ":").count();
+                let describes = words.iter().any(|w| w == command)
+                    && words
+                        .iter()
+                        .any(|w| ["GET", "READ", "QUERY", "RETURN"].contains(&w.as_str()));
+                (syntax_len == 3 && words[0] == command) || describes
+            })
+        };
+
+        Self {
+            binary_px: mentions("PB") || mentions("BINARY"),
+            compression: mentions("COMPRESS"),
+            offset: mentions("OFFSET"),
+            read_back: describes_query("PX"),
+            binary_read_back: describes_query("PB"),
+        }
+    }
+}
+
+/// The upper case words in `line`, with every `:` as a word of its own
+fn words(line: &str) -> Vec<String> {
+    line.to_uppercase()
+        .replace(':', " : ")
+        .split(|c: char| c != ':' && !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(help: &str) -> Capabilities {
+        let lines: Vec<String> = help.lines().map(String::from).collect();
+        Capabilities::from_help(&lines)
+    }
+
+    #[test]
+    fn pixelnuke() {
+        let help = "\
+PX x y: Get color at position (x,y)
+PX x y rrggbb(aa): Draw a pixel (with optional alpha channel)
+SIZE: Get canvas size
+STATS: Return statistics";
+
+        assert_eq!(
+            capabilities(help),
+            Capabilities {
+                binary_px: false,
+                compression: false,
+                offset: false,
+                read_back: true,
+                binary_read_back: false,
+            }
+        );
+    }
+
+    #[test]
+    fn breakwater() {
+        let help = "\
+Pixelflut server powered by breakwater https://github.com/sbernauer/breakwater
+Available commands:
+HELP: Show this help
+PX x y rrggbb: Color the pixel (x,y) with the given hexadecimal color rrggbb
+PX x y rrggbbaa: Color the pixel (x,y) with the given hexadecimal color rrggbb (alpha channel is ignored for now)
+PX x y gg: Color the pixel (x,y) with the hexadecimal color gggggg. Basically this is the same as the other commands, but is a more efficient way of filling white, black or gray areas
+PX x y: Get the color value of the pixel (x,y)
+SIZE: Get the size of the drawing surface, e.g. `SIZE 1920 1080`
+OFFSET x y: Apply offset (x,y) to all further pixel draws on this connection
+PBxxyyrgba: Binary version of the PX command. x and y are little-endian 16 bit coordinates, r, g, b and a are a byte each. There is *no* newline after the command";
+
+        assert_eq!(
+            capabilities(help),
+            Capabilities {
+                binary_px: true,
+                compression: false,
+                offset: true,
+                read_back: true,
+                binary_read_back: false,
+            }
+        );
+    }
+
+    #[test]
+    fn shoreline() {
+        let help = "\
+Supported commands:
+PX x y
+PX x y rrggbb
+SIZE
+HELP";
+
+        assert_eq!(
+            capabilities(help),
+            Capabilities {
+                binary_px: false,
+                compression: false,
+                offset: false,
+                read_back: true,
+                binary_read_back: false,
+            }
+        );
+    }
+
+    #[test]
+    fn mock_server() {
+        assert_eq!(
+            capabilities(crate::mockserver::HELP),
+            Capabilities {
+                binary_px: true,
+                compression: true,
+                offset: true,
+                read_back: true,
+                binary_read_back: false,
+            }
+        );
+    }
+
+    #[test]
+    fn query_described_in_words() {
+        let help = "\
+PX <x> <y> <color>  draw a pixel
+PX <x> <y>          read the color of a pixel
+PB<x><y>            read a pixel with the binary protocol";
+
+        let capabilities = capabilities(help);
+        assert!(capabilities.read_back);
+        assert!(capabilities.binary_read_back);
+    }
+
+    #[test]
+    fn no_query() {
+        let help = "\
+PX x y rrggbb: Draw a pixel
+SIZE: Get the size of the canvas
+PBXY: Get something that is not a pixel";
+
+        let capabilities = capabilities(help);
+        assert!(!capabilities.read_back);
+        assert!(!capabilities.binary_read_back);
+    }
+
+    #[test]
+    fn unrecognized_help() {
+        assert_eq!(capabilities(""), Capabilities::unknown());
+        assert_eq!(
+            capabilities("ERROR unknown command HELP"),
+            Capabilities::unknown()
+        );
+    }
+}
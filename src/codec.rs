@@ -1,27 +1,82 @@
 use std::{
-    io::{Read, Write},
+    num::NonZeroU64,
+    path::PathBuf,
     time::{Duration, Instant},
 };
 
-use crate::{pixelcollector::CompressionKind, window::Window};
+use crate::{
+    canvas::{Canvas, Region},
+    capabilities::Capabilities,
+    color::Color,
+    compression::{CompressionKind, Compressor},
+    connection::{Connection, Connector, ReadLineError, Socket},
+    dialect::{BinaryLayout, Dialect},
+    dictionary::Dictionary,
+    framequeue::{FrameQueue, QueuePolicy},
+    ratelimit::RateLimiter,
+    recording::{Recorder, RecordingHeader},
+    stats::Stats,
+    window::Window,
+};
 
 #[derive(Debug)]
 pub enum SetupError {
-    IoError(std::io::Error),
-    SizeParseError(String),
-    EnableCompressionError,
-    DataProducerError(String),
+    Io(std::io::Error),
+    /// The server did not reply to `command` within the read timeout
+    Timeout {
+        command: &'static str,
+        partial_response: String,
+    },
+    /// The server closed the connection instead of replying to `command`
+    Closed {
+        command: &'static str,
+        partial_response: String,
+    },
+    /// The server replied to `command` with something we did not expect
+    UnexpectedResponse {
+        command: &'static str,
+        response: String,
+    },
+    DataProducer(String),
+    /// The data producer needs a command that the server does not support
+    Unsupported(&'static str),
+    /// The options can't be used with the data producer
+    InvalidOptions(&'static str),
 }
 
 impl From<std::io::Error> for SetupError {
     fn from(err: std::io::Error) -> Self {
-        SetupError::IoError(err)
+        SetupError::Io(err)
+    }
+}
+
+impl SetupError {
+    fn from_read_line(command: &'static str, error: ReadLineError) -> Self {
+        match error {
+            ReadLineError::Io(e) => Self::Io(e),
+            ReadLineError::Timeout(partial_response) => Self::Timeout {
+                command,
+                partial_response,
+            },
+            ReadLineError::Closed(partial_response) => Self::Closed {
+                command,
+                partial_response,
+            },
+            ReadLineError::TooLong(response) => Self::UnexpectedResponse { command, response },
+        }
     }
 }
 
 #[derive(Debug)]
 pub enum RunError {
     Io(std::io::Error),
+    /// The data producer failed, or failed to set up again after the canvas
+    /// size changed
+    DataProducer(String),
+    /// Reading pixels back from the server failed
+    ReadBack(ReadLineError),
+    /// The server replied to a pixel query with something we did not expect
+    UnexpectedResponse(String),
 }
 
 impl From<std::io::Error> for RunError {
@@ -30,134 +85,1292 @@ impl From<std::io::Error> for RunError {
     }
 }
 
+impl From<ReadLineError> for RunError {
+    fn from(e: ReadLineError) -> Self {
+        Self::ReadBack(e)
+    }
+}
+
+impl RunError {
+    /// Whether this error means that the connection to the server was lost,
+    /// rather than that the server or the data producer misbehaved
+    fn is_connection_lost(&self) -> bool {
+        matches!(
+            self,
+            RunError::Io(_)
+                | RunError::ReadBack(
+                    ReadLineError::Io(_) | ReadLineError::Closed(_) | ReadLineError::Timeout(_)
+                )
+        )
+    }
+}
+
+/// The amount of pixel queries that are sent before reading the replies
+/// to them. Sending every query before reading any replies could make both
+/// us and the server block on a full socket buffer.
+const READ_BATCH_SIZE: usize = 1024;
+
+/// The most lines that we accept as a reply to `HELP`
+const MAX_HELP_LINES: usize = 256;
+
+#[derive(Clone)]
+pub struct ReconnectOptions {
+    /// How long to wait before the first reconnection attempt
+    pub initial_backoff: Duration,
+    /// The longest time to wait between two reconnection attempts
+    pub max_backoff: Duration,
+}
+
 #[derive(Clone)]
 pub struct CodecOptions {
     pub compression_kind: Option<CompressionKind>,
+    /// A zstd dictionary to compress with, if the server supports it
+    pub dictionary: Option<Dictionary>,
     pub binary_px: bool,
+    /// The variant of the protocol that the server speaks
+    pub dialect: Dialect,
+    /// Write the colors of text `PX` commands in lowercase hex
+    pub lowercase_hex: bool,
+    /// Send an `OFFSET` before the pixels in every chunk, so that the
+    /// coordinates of text `PX` commands are shorter
+    pub offset: bool,
+    /// How long to wait for the server to reply to a command
+    pub read_timeout: Duration,
+    /// The amount of connections that frames are split across
+    pub connections: usize,
+    /// Reconnect if the connection to the server is lost, instead of stopping
+    pub reconnect: Option<ReconnectOptions>,
+    /// The most bytes to send per second, across all connections
+    pub max_bandwidth: Option<NonZeroU64>,
+    /// The most pixels to send per second, across all connections
+    pub max_pixels_per_second: Option<NonZeroU64>,
+    /// How often to report throughput statistics
+    pub stats_interval: Duration,
+    /// A file to write throughput statistics to, in the Prometheus text format
+    pub prometheus_file: Option<PathBuf>,
+    /// A file to record every frame that is sent to, see [`crate::recording`]
+    pub record: Option<PathBuf>,
+    /// Stop after sending this many frames
+    pub max_frames: Option<u64>,
+    /// The most frames to make ahead of sending them. With 0, frames are
+    /// made and sent on the same thread.
+    pub queue_depth: usize,
+    /// What to do with new frames when the queue is full. Frames of
+    /// [incremental](DataProducer::incremental) data producers can't be
+    /// dropped.
+    pub queue_policy: QueuePolicy,
+    /// Ask the server which commands it supports with `HELP`, and turn off
+    /// the options that it doesn't support
+    pub probe_capabilities: bool,
+}
+
+impl Default for CodecOptions {
+    /// The same defaults as the command line options
+    fn default() -> Self {
+        Self {
+            compression_kind: None,
+            dictionary: None,
+            binary_px: false,
+            dialect: Dialect::GENERIC,
+            lowercase_hex: false,
+            offset: false,
+            read_timeout: Duration::from_secs(5),
+            connections: 1,
+            reconnect: None,
+            max_bandwidth: None,
+            max_pixels_per_second: None,
+            stats_interval: Duration::from_secs(10),
+            prometheus_file: None,
+            record: None,
+            max_frames: None,
+            queue_depth: 0,
+            queue_policy: QueuePolicy::Block,
+            probe_capabilities: true,
+        }
+    }
+}
+
+impl CodecOptions {
+    /// Turn off the options that the server or dialect do not support
+    fn restrict_to(&mut self, capabilities: &Capabilities) {
+        if self.binary_px && self.dialect.binary.is_none() {
+            log::warn!(
+                "The {} dialect has no binary protocol, falling back to text",
+                self.dialect
+            );
+            self.binary_px = false;
+        } else if self.binary_px && !capabilities.binary_px {
+            log::warn!("Server does not support the binary protocol, falling back to text");
+            self.binary_px = false;
+        }
+
+        if let Some(compression) = self.compression_kind {
+            if !capabilities.compression {
+                log::warn!(
+                    "Server does not support compression, not using {}",
+                    compression
+                );
+                self.compression_kind = None;
+                self.dictionary = None;
+            }
+        }
+
+        if self.offset && !capabilities.offset {
+            log::warn!("Server does not support OFFSET, sending absolute coordinates");
+            self.offset = false;
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct CodecData {
     pub window: Window,
+    /// The options in effect, after turning off what the server does not support
     pub options: CodecOptions,
+    pub capabilities: Capabilities,
+}
+
+/// Encoded pixel data, with one chunk per connection. If there are more
+/// chunks than connections, the extra chunks are sent over the connections
+/// in turn.
+#[derive(Clone, Default)]
+pub struct Frame {
+    pub chunks: Vec<Vec<u8>>,
+    /// The amount of pixels in all chunks together
+    pub pixels: usize,
+}
+
+impl Frame {
+    /// The total amount of bytes in this frame
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.iter().all(Vec::is_empty)
+    }
+
+    /// Append the chunks of `other` to the chunks of this frame, so that
+    /// they are sent after the data that is already in this frame.
+    pub fn append(&mut self, other: Frame) {
+        if self.chunks.len() < other.chunks.len() {
+            self.chunks.resize(other.chunks.len(), Vec::new());
+        }
+
+        for (chunk, other) in self.chunks.iter_mut().zip(other.chunks) {
+            chunk.extend_from_slice(&other);
+        }
+        self.pixels += other.pixels;
+    }
 }
 
 pub trait DataProducer {
     fn do_setup(&mut self, codec: &CodecData) -> Result<(), String>;
-    fn get_next_data(&mut self) -> Result<(Vec<u8>, Option<Duration>), RunError>;
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError>;
+
+    /// The part of the canvas to read back before every call to
+    /// [`DataProducer::get_next_data`], if any
+    fn readback_region(&self) -> Option<Region> {
+        None
+    }
+
+    /// The pixels in [`DataProducer::readback_region`] to read back, if
+    /// only some of them are needed. All of them are read back otherwise.
+    fn readback_pixels(&self) -> Option<&[(usize, usize)]> {
+        None
+    }
+
+    /// Called with the pixels in [`DataProducer::readback_region`], as they
+    /// were just read back from the server
+    fn update_canvas(&mut self, _canvas: &Canvas) {}
+
+    /// Whether every frame only contains what changed since the frame
+    /// before it, so that the canvas stays wrong if one is dropped
+    fn incremental(&self) -> bool {
+        false
+    }
 }
 
-pub struct Codec<T, D>
+/// The parsed reply to a pixel query
+type PixelReply = (usize, usize, Color);
+
+type Connections<C> = Vec<Connection<<C as Connector>::Socket>>;
+
+/// The connections to the server, and everything needed to send frames
+/// over them and read pixels back
+struct Transport<C>
 where
-    T: Read + Write,
-    D: DataProducer,
+    C: Connector,
 {
-    socket: T,
-    data_producer: D,
+    connector: C,
+    connections: Connections<C>,
     data: CodecData,
+    limiter: Option<RateLimiter>,
+    stats: Stats,
+    recorder: Option<Recorder>,
+    /// The amount of frames that were sent and shown for as long as the
+    /// data producer asked for
+    frames: u64,
 }
 
-impl<T, D> Codec<T, D>
+/// Why sending the frames in a queue stopped
+enum SendOutcome {
+    Finished,
+    /// The canvas size changed after reconnecting, so the data producer has
+    /// to be set up again
+    CanvasChanged,
+}
+
+impl<C> Transport<C>
 where
-    T: Read + Write,
-    D: DataProducer,
+    C: Connector,
 {
-    pub fn data(&self) -> &CodecData {
-        &self.data
+    /// Open all connections and perform the handshake on each of them. Any
+    /// `options` that the server does not support are turned off.
+    fn connect(
+        connector: &C,
+        options: &mut CodecOptions,
+    ) -> Result<(Connections<C>, Window, Capabilities), SetupError> {
+        let mut connections = Vec::with_capacity(options.connections);
+        for _ in 0..options.connections.max(1) {
+            let socket = connector.connect()?;
+            socket.set_read_timeout(Some(options.read_timeout))?;
+            connections.push(Connection::new(socket));
+        }
+
+        let (window, capabilities) = if options.probe_capabilities {
+            let (window, capabilities) = Self::read_help_and_size(&mut connections[0])?;
+            log::debug!("Server capabilities: {:?}", capabilities);
+            options.restrict_to(&capabilities);
+            (window, capabilities)
+        } else {
+            (
+                Self::read_size(&mut connections[0])?,
+                Capabilities::unknown(),
+            )
+        };
+
+        if let Some(compression) = options.compression_kind {
+            for connection in connections.iter_mut() {
+                Self::enable_compression(connection, compression, options.dictionary.as_ref())?;
+            }
+        }
+
+        Ok((connections, window, capabilities))
     }
 
-    pub fn new(
-        mut socket: T,
-        mut data_producer: D,
-        options: CodecOptions,
-    ) -> Result<Self, SetupError> {
-        socket.write("SIZE\n".as_bytes())?;
-        let result = &mut [0u8; 128];
+    /// Keep trying to connect to the server, with exponential backoff.
+    /// Returns whether the size of the canvas changed.
+    fn reconnect(&mut self, reconnect: &ReconnectOptions) -> bool {
+        let mut backoff = reconnect.initial_backoff;
 
-        std::thread::sleep(std::time::Duration::from_millis(500));
+        let window = loop {
+            log::info!("Reconnecting in {} ms", backoff.as_millis());
+            std::thread::sleep(backoff);
 
-        let len = socket.read(result)?;
+            match Self::connect(&self.connector, &mut self.data.options) {
+                Ok((connections, window, capabilities)) => {
+                    self.connections = connections;
+                    self.data.capabilities = capabilities;
+                    break window;
+                }
+                Err(e) => {
+                    log::warn!("Failed to reconnect: {:?}", e);
+                    backoff = (backoff * 2).min(reconnect.max_backoff);
+                }
+            }
+        };
 
-        let mut size_result = if let Ok(result) = String::from_utf8((&result[..len]).to_vec()) {
-            result
-        } else {
-            return Err(SetupError::SizeParseError(String::from(
-                "Could not read size.",
-            )));
+        log::info!("Reconnected");
+
+        if window == self.data.window {
+            return false;
+        }
+
+        log::info!(
+            "Canvas size changed to x: {}, y: {}",
+            window.get_x(),
+            window.get_y()
+        );
+        self.data.window = window;
+        true
+    }
+
+    /// Send `command`, and read the line that the server replies with.
+    fn request(
+        connection: &mut Connection<C::Socket>,
+        command: &'static str,
+        data: &[u8],
+    ) -> Result<String, SetupError> {
+        connection.send(data)?;
+        connection
+            .read_line()
+            .map_err(|e| SetupError::from_read_line(command, e))
+    }
+
+    fn read_size(connection: &mut Connection<C::Socket>) -> Result<Window, SetupError> {
+        let response = Self::request(connection, "SIZE", b"SIZE\n")?;
+
+        Self::parse_size(&response).ok_or(SetupError::UnexpectedResponse {
+            command: "SIZE",
+            response,
+        })
+    }
+
+    /// Send `HELP` followed by `SIZE`. Every line before the reply to `SIZE`
+    /// is part of the reply to `HELP`, which is empty for servers that
+    /// don't support it.
+    fn read_help_and_size(
+        connection: &mut Connection<C::Socket>,
+    ) -> Result<(Window, Capabilities), SetupError> {
+        let mut help = Vec::new();
+        let mut response = Self::request(connection, "HELP", b"HELP\nSIZE\n")?;
+
+        loop {
+            if let Some(window) = Self::parse_size(&response) {
+                return Ok((window, Capabilities::from_help(&help)));
+            }
+
+            if help.len() == MAX_HELP_LINES {
+                return Err(SetupError::UnexpectedResponse {
+                    command: "HELP",
+                    response,
+                });
+            }
+
+            help.push(response);
+            response = connection
+                .read_line()
+                .map_err(|e| SetupError::from_read_line("HELP", e))?;
+        }
+    }
+
+    /// Parse a `SIZE <width> <height>` line
+    fn parse_size(response: &str) -> Option<Window> {
+        let mut parts = response.split_whitespace();
+        match (
+            parts.next(),
+            parts.next().map(str::parse),
+            parts.next().map(str::parse),
+            parts.next(),
+        ) {
+            (Some("SIZE"), Some(Ok(x_width)), Some(Ok(y_height)), None) => {
+                Some(Window { x_width, y_height })
+            }
+            _ => None,
+        }
+    }
+
+    fn enable_compression(
+        connection: &mut Connection<C::Socket>,
+        compression: CompressionKind,
+        dictionary: Option<&Dictionary>,
+    ) -> Result<(), SetupError> {
+        let command = match dictionary {
+            Some(dictionary) => compression.dictionary_compression_string(dictionary.id()),
+            None => compression.compression_string(),
         };
+        let response = Self::request(connection, "COMPRESS", &command)?;
 
-        size_result.pop();
+        // Servers either acknowledge with a bare `COMPRESS`, or echo the algorithm.
+        // Servers that can use the dictionary echo its ID as well.
+        let expected = format!("COMPRESS {}", compression.name());
+        let dictionary = match dictionary {
+            Some(dictionary)
+                if response.eq_ignore_ascii_case(&format!("{} {}", expected, dictionary.id())) =>
+            {
+                Some(dictionary)
+            }
+            _ if response != "COMPRESS" && !response.eq_ignore_ascii_case(&expected) => {
+                return Err(SetupError::UnexpectedResponse {
+                    command: "COMPRESS",
+                    response,
+                });
+            }
+            Some(dictionary) => {
+                log::warn!(
+                    "Server does not support dictionary {}, compressing without it",
+                    dictionary.id()
+                );
+                None
+            }
+            None => None,
+        };
 
-        let size_result = if size_result.ends_with('\r') {
-            &size_result[..size_result.len() - 1]
-        } else {
-            &size_result
+        connection.compressor = Some(Compressor::new(compression, dictionary)?);
+
+        Ok(())
+    }
+
+    /// Write every chunk of `frame` to its connection, and flush the
+    /// compressed stream of every connection at the end of the frame. If
+    /// there are multiple connections, the chunks are written concurrently.
+    /// Returns the amount of bytes that were written.
+    fn write_frame(&mut self, frame: &Frame) -> Result<usize, RunError> {
+        let binary = self
+            .data
+            .options
+            .dialect
+            .binary
+            .filter(|_| self.data.options.binary_px);
+        let limiter = self.limiter.as_ref();
+        let connections = &mut self.connections;
+
+        if let [connection] = connections.as_mut_slice() {
+            let mut sent = 0;
+            for chunk in frame.chunks.iter() {
+                sent += Self::write_chunk(connection, chunk, limiter, binary)?;
+            }
+            return Ok(sent + Self::flush(connection, limiter)?);
+        }
+
+        // Every chunk sets its own offset, so a connection can send several
+        // of them, e.g. when a recording was made with more connections
+        let connection_count = connections.len();
+        let sent = std::thread::scope(|scope| {
+            let writers: Vec<_> = connections
+                .iter_mut()
+                .take(frame.chunks.len())
+                .enumerate()
+                .map(|(index, connection)| {
+                    let chunks = frame.chunks.iter().skip(index).step_by(connection_count);
+                    scope.spawn(move || {
+                        let mut sent = 0;
+                        for chunk in chunks {
+                            sent += Self::write_chunk(connection, chunk, limiter, binary)?;
+                        }
+                        Ok(sent + Self::flush(connection, limiter)?)
+                    })
+                })
+                .collect();
+
+            writers
+                .into_iter()
+                .map(|writer| writer.join().expect("Writer thread panicked"))
+                .sum::<std::io::Result<usize>>()
+        })?;
+
+        Ok(sent)
+    }
+
+    /// Write `chunk` to `connection`, without flushing the compressed
+    /// stream. If there is a rate limiter, the chunk is written in pieces,
+    /// waiting for the limiter before every piece. Returns the amount of
+    /// bytes that were written.
+    fn write_chunk(
+        connection: &mut Connection<C::Socket>,
+        chunk: &[u8],
+        limiter: Option<&RateLimiter>,
+        binary: Option<BinaryLayout>,
+    ) -> std::io::Result<usize> {
+        let limiter = match limiter {
+            Some(limiter) => limiter,
+            None => return connection.write(chunk),
         };
 
-        let parts: Vec<&str> = size_result.split(" ").collect();
+        let mut total = 0;
+        for (piece, pixels) in limiter.split(chunk, binary) {
+            limiter.wait();
+            let sent = connection.write(piece)?;
+            limiter.sent(sent, pixels);
+            total += sent;
+        }
 
-        let window = if parts.len() > 2 {
-            let (x_width, y_height) = match (parts[1].trim().parse(), parts[2].trim().parse()) {
-                (Ok(x), Ok(y)) => (x, y),
-                _ => {
-                    return Err(SetupError::SizeParseError(String::from(
-                        "Could not parse width and height.",
-                    )));
-                }
-            };
+        Ok(total)
+    }
+
+    /// Flush the compressed stream of `connection` at the end of a frame,
+    /// counting what is sent against the rate limiter. Returns the amount of
+    /// bytes that were written.
+    fn flush(
+        connection: &mut Connection<C::Socket>,
+        limiter: Option<&RateLimiter>,
+    ) -> std::io::Result<usize> {
+        let sent = connection.flush()?;
+        if let Some(limiter) = limiter {
+            limiter.sent(sent, 0);
+        }
+        Ok(sent)
+    }
+
+    /// Read the colors of all pixels in `region` back from the server, by
+    /// sending `PX x y` queries. The rows of the region are divided across
+    /// all connections. The binary protocol is used for the queries if it
+    /// is enabled, and the server supports binary read-back.
+    pub fn read_region(&mut self, region: Region) -> Result<Canvas, RunError> {
+        self.read_pixels_in(region, region.coordinates())
+    }
+
+    /// Read the colors of `pixels` back from the server, like
+    /// [`Transport::read_region`]. Pixels outside of `region` are skipped,
+    /// and all other pixels of `region` are left unread in the canvas.
+    pub fn read_pixels_in(
+        &mut self,
+        region: Region,
+        pixels: impl Iterator<Item = (usize, usize)>,
+    ) -> Result<Canvas, RunError> {
+        let region = region.clamp(&self.data.window);
+        let binary = self.data.options.binary_px && self.data.capabilities.binary_read_back;
+        let offset = self.data.options.offset;
 
-            let window = Window { x_width, y_height };
-            window
+        let mut coordinates = vec![Vec::new(); self.connections.len()];
+        for (x, y) in pixels.filter(|(x, y)| region.contains(*x, *y)) {
+            coordinates[y % self.connections.len()].push((x, y));
+        }
+
+        let replies = if let [connection] = self.connections.as_mut_slice() {
+            vec![Self::read_pixels(
+                connection,
+                coordinates.remove(0).into_iter(),
+                binary,
+                offset,
+            )?]
         } else {
-            return Err(SetupError::SizeParseError(String::from(
-                "Did not receive enough parts.",
-            )));
+            std::thread::scope(|scope| {
+                let readers: Vec<_> = self
+                    .connections
+                    .iter_mut()
+                    .zip(coordinates)
+                    .map(|(connection, coordinates)| {
+                        scope.spawn(move || {
+                            Self::read_pixels(connection, coordinates.into_iter(), binary, offset)
+                        })
+                    })
+                    .collect();
+
+                readers
+                    .into_iter()
+                    .map(|reader| reader.join().expect("Reader thread panicked"))
+                    .collect::<Result<Vec<_>, _>>()
+            })?
         };
 
-        if let Some(compression) = options.compression_kind {
-            socket.write_all(&compression.compression_string())?;
+        let mut canvas = Canvas::new(region);
+        for (x, y, color) in replies.into_iter().flatten() {
+            if !canvas.set(x, y, color) {
+                return Err(RunError::UnexpectedResponse(format!(
+                    "PX {} {} {:x}",
+                    x, y, color
+                )));
+            }
+        }
 
-            let mut compress_resp = [0u8; 12];
+        Ok(canvas)
+    }
 
-            socket.read(&mut compress_resp)?;
+    /// Query the color of every pixel in `coordinates` over `connection`,
+    /// in batches of [`READ_BATCH_SIZE`] pixels. If `offset` is set, the
+    /// offset that the last frame left behind is reset first.
+    fn read_pixels(
+        connection: &mut Connection<C::Socket>,
+        coordinates: impl Iterator<Item = (usize, usize)>,
+        binary: bool,
+        offset: bool,
+    ) -> Result<Vec<PixelReply>, RunError> {
+        let mut replies = Vec::new();
+        let mut coordinates = coordinates.peekable();
 
-            if compress_resp.starts_with(b"COMPRESS\r\n")
-                && compress_resp.starts_with(b"COMPRESS\n")
-            {
-                return Err(SetupError::EnableCompressionError);
+        if offset {
+            connection.send(b"OFFSET 0 0\n")?;
+        }
+
+        while coordinates.peek().is_some() {
+            let mut queries = Vec::new();
+            let mut count = 0;
+            for (x, y) in coordinates.by_ref().take(READ_BATCH_SIZE) {
+                if binary {
+                    queries.extend_from_slice(b"PB");
+                    queries.extend_from_slice(&(x as u16).to_le_bytes());
+                    queries.extend_from_slice(&(y as u16).to_le_bytes());
+                } else {
+                    queries.extend_from_slice(format!("PX {} {}\n", x, y).as_bytes());
+                }
+                count += 1;
+            }
+
+            connection.send(&queries)?;
+
+            for _ in 0..count {
+                let reply = if binary {
+                    Self::read_binary_reply(connection)?
+                } else {
+                    Self::read_text_reply(connection)?
+                };
+                replies.push(reply);
+            }
+        }
+
+        Ok(replies)
+    }
+
+    /// Read a `PX x y rrggbb` or `PX x y rrggbbaa` reply
+    fn read_text_reply(connection: &mut Connection<C::Socket>) -> Result<PixelReply, RunError> {
+        let line = connection.read_line()?;
+
+        let mut parts = line.split_whitespace();
+        match (
+            parts.next(),
+            parts.next().map(str::parse),
+            parts.next().map(str::parse),
+            parts.next().map(str::parse::<Color>),
+            parts.next(),
+        ) {
+            (Some("PX"), Some(Ok(x)), Some(Ok(y)), Some(Ok(color)), None) => Ok((x, y, color)),
+            _ => Err(RunError::UnexpectedResponse(line)),
+        }
+    }
+
+    /// Read a `PB` reply: `PB`, followed by the little endian `u16` x and y
+    /// coordinates and the RGBA color of the pixel
+    fn read_binary_reply(connection: &mut Connection<C::Socket>) -> Result<PixelReply, RunError> {
+        let reply = connection.read_exact(10)?;
+
+        if &reply[..2] != b"PB" {
+            return Err(RunError::UnexpectedResponse(
+                String::from_utf8_lossy(&reply).into_owned(),
+            ));
+        }
+
+        let x = u16::from_le_bytes([reply[2], reply[3]]) as usize;
+        let y = u16::from_le_bytes([reply[4], reply[5]]) as usize;
+        let color = Color::from_rgba(reply[6], reply[7], reply[8], Some(reply[9]));
+
+        Ok((x, y, color))
+    }
+
+    /// Reconnect after `error` if reconnecting is enabled and the connection
+    /// was lost, and return `error` otherwise. Returns whether the size of
+    /// the canvas changed.
+    fn handle_connection_error(&mut self, error: RunError) -> Result<bool, RunError> {
+        match self.data.options.reconnect.clone() {
+            Some(reconnect) if error.is_connection_lost() => {
+                log::warn!("Lost connection: {:?}", error);
+                Ok(self.reconnect(&reconnect))
             }
+            _ => Err(error),
         }
+    }
+
+    /// Write `frame`, and write it again after reconnecting if the
+    /// connection was lost, because the frames after it may only contain
+    /// what changed since. Returns when the successful write started and the
+    /// amount of bytes sent, or `None` if the size of the canvas changed, so
+    /// that the frame no longer fits.
+    fn send_frame(&mut self, frame: &Frame) -> Result<Option<(Instant, usize)>, RunError> {
+        loop {
+            let start = Instant::now();
+            match self.write_frame(frame) {
+                Ok(sent) => return Ok(Some((start, sent))),
+                Err(e) => {
+                    if self.handle_connection_error(e)? {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Count a frame that started to be written at `start` in the
+    /// statistics and the recording. Returns how long writing it took.
+    fn frame_sent(
+        &mut self,
+        frame: &Frame,
+        start: Instant,
+        sent: usize,
+    ) -> Result<Duration, RunError> {
+        let send_duration = start.elapsed();
+
+        self.connector.frame_sent();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(frame, start)?;
+        }
+        self.stats
+            .frame_sent(sent, frame.len(), frame.pixels, send_duration);
+
+        Ok(send_duration)
+    }
+
+    /// Count a frame that was shown for as long as the data producer asked
+    /// for. Returns whether the codec should stop, because it sent
+    /// `max_frames` frames.
+    fn count_frame(&mut self) -> bool {
+        self.frames += 1;
+        self.data.options.max_frames == Some(self.frames)
+    }
+
+    /// Send the frames in `queue` until it is closed and empty. Every frame
+    /// is due the time the data producer asked for after the previous one
+    /// was due, regardless of how long sending took.
+    fn send_queued(&mut self, queue: &FrameQueue) -> Result<SendOutcome, RunError> {
+        let mut deadline = None;
 
-        let data = CodecData { window, options };
+        while let Some((frame, next_data)) = queue.pop() {
+            self.stats.report_if_due();
+            self.stats.frames_dropped(queue.take_dropped());
+
+            let due = *deadline.get_or_insert_with(Instant::now);
+
+            let (start, sent) = match self.send_frame(&frame)? {
+                Some(sent) => sent,
+                None => return Ok(SendOutcome::CanvasChanged),
+            };
+            self.frame_sent(&frame, start, sent)?;
+
+            let next_data = match next_data {
+                Some(next_data) => next_data,
+                None => break,
+            };
+
+            // If sending fell behind by more than a frame, start over from
+            // now instead of sending frames back to back to catch up
+            let next_due = due + next_data;
+            let now = Instant::now();
+            deadline = Some(if next_due > now {
+                std::thread::sleep(next_due - now);
+                self.stats.slept(next_due - now);
+                next_due
+            } else if now - next_due > next_data {
+                now
+            } else {
+                next_due
+            });
+
+            if self.count_frame() {
+                break;
+            }
+        }
+
+        Ok(SendOutcome::Finished)
+    }
+}
+
+pub struct Codec<C, D>
+where
+    C: Connector,
+    D: DataProducer,
+{
+    transport: Transport<C>,
+    data_producer: D,
+}
+
+impl<C, D> Codec<C, D>
+where
+    C: Connector,
+    D: DataProducer,
+{
+    pub fn data(&self) -> &CodecData {
+        &self.transport.data
+    }
+
+    /// Set up a codec that sends frames across `options.connections`
+    /// connections made with `connector`.
+    pub fn new(
+        connector: C,
+        mut data_producer: D,
+        mut options: CodecOptions,
+    ) -> Result<Self, SetupError> {
+        let (connections, window, capabilities) = Transport::connect(&connector, &mut options)?;
+
+        let data = CodecData {
+            window,
+            options,
+            capabilities,
+        };
 
         data_producer
             .do_setup(&data)
-            .map_err(|e| SetupError::DataProducerError(e))?;
+            .map_err(SetupError::DataProducer)?;
+
+        if data_producer.readback_region().is_some() && !data.capabilities.read_back {
+            return Err(SetupError::Unsupported("PX x y"));
+        }
+
+        if data.options.queue_depth > 0
+            && data.options.queue_policy == QueuePolicy::DropOldest
+            && data_producer.incremental()
+        {
+            return Err(SetupError::InvalidOptions(
+                "Frames that only contain changes can't be dropped from the queue",
+            ));
+        }
+
+        let limiter = RateLimiter::new(
+            data.options.max_bandwidth,
+            data.options.max_pixels_per_second,
+        );
+
+        let stats = Stats::new(
+            data.options.stats_interval,
+            data.options.prometheus_file.clone(),
+        );
+
+        let recorder = match &data.options.record {
+            Some(path) => Some(Recorder::create(path, &RecordingHeader::from(&data))?),
+            None => None,
+        };
 
         Ok(Self {
-            socket,
-            data,
+            transport: Transport {
+                connector,
+                connections,
+                data,
+                limiter,
+                stats,
+                recorder,
+                frames: 0,
+            },
             data_producer,
         })
     }
 
-    pub fn run(mut self) -> Result<(), RunError> {
+    /// Read the colors of all pixels in `region` back from the server
+    pub fn read_region(&mut self, region: Region) -> Result<Canvas, RunError> {
+        self.transport.read_region(region)
+    }
+
+    /// Read back the region that the data producer asked for, if any
+    fn read_canvas(&mut self) -> Result<(), RunError> {
+        if let Some(region) = self.data_producer.readback_region() {
+            let canvas = match self.data_producer.readback_pixels() {
+                Some(pixels) => self
+                    .transport
+                    .read_pixels_in(region, pixels.iter().copied())?,
+                None => self.read_region(region)?,
+            };
+            self.data_producer.update_canvas(&canvas);
+        }
+
+        Ok(())
+    }
+
+    /// Reconnect after `error` if reconnecting is enabled and the connection
+    /// was lost, and return `error` otherwise. If the size of the canvas
+    /// changed, the data producer is set up again.
+    fn handle_connection_error(&mut self, error: RunError) -> Result<(), RunError> {
+        if self.transport.handle_connection_error(error)? {
+            self.set_up_again()?;
+        }
+
+        Ok(())
+    }
+
+    /// Set the data producer up again, after the size of the canvas changed
+    fn set_up_again(&mut self) -> Result<(), RunError> {
+        self.data_producer
+            .do_setup(&self.transport.data)
+            .map_err(RunError::DataProducer)
+    }
+
+    /// Send frames until the data producer is done. Frames are made on a
+    /// separate thread and queued, unless the queue depth is 0 or the data
+    /// producer reads the canvas back, because then every frame depends on
+    /// the pixels read back right before it.
+    pub fn run(mut self) -> Result<(), RunError>
+    where
+        D: Send,
+    {
+        let options = &self.transport.data.options;
+        let result = if options.queue_depth == 0 || self.data_producer.readback_region().is_some() {
+            self.run_frames()
+        } else {
+            self.run_queued(options.queue_depth, options.queue_policy)
+        };
+
+        self.transport.stats.report();
+        result
+    }
+
+    fn run_frames(&mut self) -> Result<(), RunError> {
         loop {
-            let (data, next_data) = self.data_producer.get_next_data()?;
-            let start = Instant::now();
+            self.transport.stats.report_if_due();
+
+            if let Err(e) = self.read_canvas() {
+                self.handle_connection_error(e)?;
+                continue;
+            }
 
-            self.socket.write_all(&data)?;
+            let (frame, next_data) = self.data_producer.get_next_data()?;
 
-            let send_duration = Instant::now().duration_since(start);
+            let (start, sent) = match self.transport.send_frame(&frame)? {
+                Some(sent) => sent,
+                None => {
+                    self.set_up_again()?;
+                    continue;
+                }
+            };
+
+            let send_duration = self.transport.frame_sent(&frame, start, sent)?;
 
             if let Some(next_data) = next_data {
                 if next_data > send_duration {
                     let sleep_duration = next_data - send_duration;
                     std::thread::sleep(sleep_duration);
+                    self.transport.stats.slept(sleep_duration);
                 }
             } else {
                 break Ok(());
             }
+
+            if self.transport.count_frame() {
+                break Ok(());
+            }
+        }
+    }
+
+    /// Make frames on a separate thread, and send them from this one through
+    /// a queue of `depth` frames
+    fn run_queued(&mut self, depth: usize, policy: QueuePolicy) -> Result<(), RunError>
+    where
+        D: Send,
+    {
+        loop {
+            let queue = FrameQueue::new(depth, policy);
+            let data_producer = &mut self.data_producer;
+            let transport = &mut self.transport;
+
+            let outcome = std::thread::scope(|scope| {
+                let producer = scope.spawn(|| {
+                    let result = Self::produce(data_producer, &queue, policy);
+                    queue.close();
+                    result
+                });
+
+                let outcome = transport.send_queued(&queue);
+                // Stop the producer if the sender stopped first
+                queue.close();
+
+                let produced = producer.join().expect("Producer thread panicked");
+                outcome.and_then(|outcome| produced.map(|_| outcome))
+            })?;
+
+            match outcome {
+                SendOutcome::Finished => return Ok(()),
+                SendOutcome::CanvasChanged => self.set_up_again()?,
+            }
         }
     }
+
+    /// Put frames in `queue` until the data producer is done, or the queue
+    /// is closed. If the queue drops frames, frames are made at the rate
+    /// the data producer asks for, so that only the frames that the sender
+    /// can't keep up with are dropped. Otherwise, frames are made as fast as
+    /// the queue accepts them.
+    fn produce(
+        data_producer: &mut D,
+        queue: &FrameQueue,
+        policy: QueuePolicy,
+    ) -> Result<(), RunError> {
+        let mut deadline = Instant::now();
+        loop {
+            let (frame, next_data) = data_producer.get_next_data()?;
+
+            let next_data = match next_data {
+                Some(next_data) => next_data,
+                None => {
+                    queue.push((frame, None));
+                    return Ok(());
+                }
+            };
+
+            if !queue.push((frame, Some(next_data))) {
+                return Ok(());
+            }
+
+            if policy == QueuePolicy::DropOldest {
+                deadline = (deadline + next_data).max(Instant::now());
+                std::thread::sleep(deadline - Instant::now());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Read, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    /// A socket that replies with `replies`, and records what was sent
+    struct ReplySocket {
+        replies: Cursor<Vec<u8>>,
+        sent: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Read for ReplySocket {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.replies.read(buf)
+        }
+    }
+
+    impl Write for ReplySocket {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.sent.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Socket for ReplySocket {
+        fn set_read_timeout(&self, _timeout: Option<Duration>) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Connects to a server that only replies to `SIZE`
+    struct ReplyConnector {
+        sent: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Connector for ReplyConnector {
+        type Socket = ReplySocket;
+
+        fn connect(&self) -> std::io::Result<ReplySocket> {
+            Ok(ReplySocket {
+                replies: Cursor::new(b"SIZE 100 100\n".to_vec()),
+                sent: self.sent.clone(),
+            })
+        }
+    }
+
+    /// Sends `count` frames as fast as possible, each with a single pixel
+    /// whose x coordinate is the number of the frame
+    struct Counter {
+        next: usize,
+        count: usize,
+        incremental: bool,
+    }
+
+    impl Counter {
+        fn new(count: usize, incremental: bool) -> Self {
+            Self {
+                next: 0,
+                count,
+                incremental,
+            }
+        }
+    }
+
+    impl DataProducer for Counter {
+        fn do_setup(&mut self, _codec: &CodecData) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
+            let frame = Frame {
+                chunks: vec![format!("PX {} 0 FF0000\n", self.next).into_bytes()],
+                pixels: 1,
+            };
+            self.next += 1;
+            let next_data = Some(Duration::ZERO).filter(|_| self.next < self.count);
+            Ok((frame, next_data))
+        }
+
+        fn incremental(&self) -> bool {
+            self.incremental
+        }
+    }
+
+    fn queued(depth: usize, policy: QueuePolicy) -> CodecOptions {
+        CodecOptions {
+            queue_depth: depth,
+            queue_policy: policy,
+            probe_capabilities: false,
+            stats_interval: Duration::from_secs(60),
+            ..CodecOptions::default()
+        }
+    }
+
+    type TestTransport = Transport<ReplyConnector>;
+
+    fn connection(replies: &[u8]) -> (Connection<ReplySocket>, Arc<Mutex<Vec<u8>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let connection = Connection::new(ReplySocket {
+            replies: Cursor::new(replies.to_vec()),
+            sent: sent.clone(),
+        });
+        (connection, sent)
+    }
+
+    fn red(a: Option<u8>) -> Color {
+        Color::from_rgba(0xFF, 0x00, 0x00, a)
+    }
+
+    #[test]
+    fn text_reply() {
+        let (mut connection, _) = connection(b"PX 12 3 FF0000\nPX 4 5 FF000080\n");
+        assert_eq!(
+            TestTransport::read_text_reply(&mut connection).unwrap(),
+            (12, 3, red(Some(0xFF)))
+        );
+        assert_eq!(
+            TestTransport::read_text_reply(&mut connection).unwrap(),
+            (4, 5, red(Some(0x80)))
+        );
+    }
+
+    #[test]
+    fn unexpected_text_replies() {
+        for reply in [
+            &b"ERROR out of bounds\n"[..],
+            b"PX 12 3\n",
+            b"PX 12 3 FF0000 extra\n",
+            b"PX -1 3 FF0000\n",
+            b"PX 12 3 GG0000\n",
+        ] {
+            match TestTransport::read_text_reply(&mut connection(reply).0) {
+                Err(RunError::UnexpectedResponse(_)) => {}
+                other => panic!("Expected an unexpected response, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn binary_reply() {
+        let (mut connection, _) =
+            connection(&[b'P', b'B', 0x2C, 0x01, 3, 0, 0xFF, 0x00, 0x00, 0x80]);
+        assert_eq!(
+            TestTransport::read_binary_reply(&mut connection).unwrap(),
+            (300, 3, red(Some(0x80)))
+        );
+    }
+
+    #[test]
+    fn unexpected_binary_reply() {
+        let (mut connection, _) = connection(b"PX 12 3 FF0000\n");
+        match TestTransport::read_binary_reply(&mut connection) {
+            Err(RunError::UnexpectedResponse(_)) => {}
+            other => panic!("Expected an unexpected response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_binary_reply() {
+        let (mut connection, _) = connection(&[b'P', b'B', 12, 0, 3]);
+        assert!(TestTransport::read_binary_reply(&mut connection).is_err());
+    }
+
+    #[test]
+    fn read_pixels_text() {
+        let (mut connection, sent) = connection(b"PX 0 0 FF0000\nPX 1 0 00FF00\n");
+        let replies = TestTransport::read_pixels(
+            &mut connection,
+            vec![(0, 0), (1, 0)].into_iter(),
+            false,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(
+            replies,
+            [
+                (0, 0, red(Some(0xFF))),
+                (1, 0, Color::from_rgba(0x00, 0xFF, 0x00, Some(0xFF))),
+            ]
+        );
+        assert_eq!(*sent.lock().unwrap(), b"OFFSET 0 0\nPX 0 0\nPX 1 0\n");
+    }
+
+    #[test]
+    fn read_pixels_binary() {
+        let (mut connection, sent) = connection(&[b'P', b'B', 1, 0, 2, 0, 0xFF, 0x00, 0x00, 0xFF]);
+        let replies =
+            TestTransport::read_pixels(&mut connection, vec![(1, 2)].into_iter(), true, false)
+                .unwrap();
+
+        assert_eq!(replies, [(1, 2, red(Some(0xFF)))]);
+        assert_eq!(*sent.lock().unwrap(), [b'P', b'B', 1, 0, 2, 0]);
+    }
+
+    #[test]
+    fn queued_frames_arrive_in_order() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let connector = ReplyConnector { sent: sent.clone() };
+        Codec::new(
+            connector,
+            Counter::new(1000, true),
+            queued(2, QueuePolicy::Block),
+        )
+        .unwrap()
+        .run()
+        .unwrap();
+
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        let frames: Vec<usize> = sent
+            .lines()
+            .filter_map(|line| line.strip_prefix("PX "))
+            .map(|line| line.split(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(frames, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn incremental_frames_are_not_dropped() {
+        let connector = || ReplyConnector {
+            sent: Arc::default(),
+        };
+
+        let options = queued(2, QueuePolicy::DropOldest);
+        match Codec::new(connector(), Counter::new(10, true), options.clone()) {
+            Err(SetupError::InvalidOptions(_)) => {}
+            Err(e) => panic!("Expected invalid options, got {:?}", e),
+            Ok(_) => panic!("Expected invalid options"),
+        }
+
+        assert!(Codec::new(connector(), Counter::new(10, false), options).is_ok());
+
+        // Without a queue, nothing is dropped
+        let options = queued(0, QueuePolicy::DropOldest);
+        assert!(Codec::new(connector(), Counter::new(10, true), options).is_ok());
+    }
+
+    /// Sends a single frame with `chunks` chunks, each with one pixel
+    struct Chunks(usize);
+
+    impl DataProducer for Chunks {
+        fn do_setup(&mut self, _codec: &CodecData) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
+            let chunks = (0..self.0)
+                .map(|x| format!("PX {} 0 FF0000\n", x).into_bytes())
+                .collect();
+            Ok((
+                Frame {
+                    chunks,
+                    pixels: self.0,
+                },
+                None,
+            ))
+        }
+    }
+
+    #[test]
+    fn more_chunks_than_connections() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let connector = ReplyConnector { sent: sent.clone() };
+        let options = CodecOptions {
+            connections: 2,
+            ..queued(0, QueuePolicy::Block)
+        };
+        Codec::new(connector, Chunks(5), options)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        let mut pixels: Vec<&str> = sent.lines().filter(|line| line.starts_with("PX")).collect();
+        pixels.sort_unstable();
+        assert_eq!(
+            pixels,
+            (0..5)
+                .map(|x| format!("PX {} 0 FF0000", x))
+                .collect::<Vec<_>>()
+        );
+    }
 }
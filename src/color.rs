@@ -2,7 +2,7 @@ use std::{fmt::LowerHex, str::FromStr};
 
 use rand::{thread_rng, RngCore};
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -60,8 +60,8 @@ impl FromStr for Color {
             return Ok(Color::random());
         }
 
-        if len != 6 && len != 8 {
-            return Err(String::from("Color must be 6 or 8 characters in length"));
+        if len != 2 && len != 6 && len != 8 {
+            return Err(String::from("Color must be 2, 6 or 8 characters in length"));
         }
 
         let mut digits = Vec::new();
@@ -73,17 +73,58 @@ impl FromStr for Color {
             }
         }
 
+        // A single byte is a shade of gray
+        if len == 2 {
+            let value = (digits[0] * 16 + digits[1]) as u8;
+            return Ok(Color::from_rgba(value, value, value, Some(0xFF)));
+        }
+
         let mut color = Color {
-            r: (digits[0] + digits[1] * 16) as u8,
-            g: (digits[2] + digits[3] * 16) as u8,
-            b: (digits[4] + digits[5] * 16) as u8,
+            r: (digits[0] * 16 + digits[1]) as u8,
+            g: (digits[2] * 16 + digits[3]) as u8,
+            b: (digits[4] * 16 + digits[5]) as u8,
             a: Some(0xFF),
         };
 
         if len == 8 {
-            color.a = Some((digits[6] + digits[7] * 16) as u8)
+            color.a = Some((digits[6] * 16 + digits[7]) as u8)
         }
 
         Ok(color)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_high_nibble_first() {
+        assert_eq!(
+            "1A2B3C".parse(),
+            Ok(Color::from_rgba(0x1A, 0x2B, 0x3C, Some(0xFF)))
+        );
+        assert_eq!(
+            "1a2b3c4d".parse(),
+            Ok(Color::from_rgba(0x1A, 0x2B, 0x3C, Some(0x4D)))
+        );
+        assert_eq!(
+            "1E".parse(),
+            Ok(Color::from_rgba(0x1E, 0x1E, 0x1E, Some(0xFF)))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for color in ["", "1", "1A2B", "1A2B3C4", "1A2B3G"] {
+            assert!(color.parse::<Color>().is_err(), "{} parsed", color);
+        }
+    }
+
+    #[test]
+    fn format_round_trip() {
+        let color = Color::from_rgba(0x1A, 0x2B, 0x3C, Some(0x4D));
+        assert_eq!(format!("{:x}", color), "1a2b3c4d");
+        assert_eq!(format!("{:x}", color).parse(), Ok(color));
+    }
+}
@@ -0,0 +1,187 @@
+use std::{io::Write, str::FromStr};
+
+use crate::dictionary::Dictionary;
+use flate2::{
+    write::{DeflateEncoder, GzEncoder},
+    Compression,
+};
+
+/// A compression algorithm, along with the level to compress at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressionKind {
+    Zstd(i32),
+    Deflate(u32),
+    Gzip(u32),
+}
+
+impl FromStr for CompressionKind {
+    type Err = String;
+
+    /// Parse `kind` or `kind:level`, e.g. `zstd` or `gzip:9`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        let (kind, level) = match lower.split_once(':') {
+            Some((kind, level)) => (kind, Some(level)),
+            None => (lower.as_str(), None),
+        };
+
+        let kind = match kind {
+            "zstd" => Self::Zstd(Self::parse_level(
+                level,
+                1,
+                zstd::compression_level_range(),
+            )?),
+            "deflate" => Self::Deflate(Self::parse_level(level, 6, 0..=9)?),
+            "gzip" => Self::Gzip(Self::parse_level(level, 6, 0..=9)?),
+            _ => return Err(format!("unknown compression type {}", kind)),
+        };
+        Ok(kind)
+    }
+}
+
+impl std::fmt::Display for CompressionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionKind::Zstd(level) => write!(f, "zstd:{}", level),
+            CompressionKind::Deflate(level) => write!(f, "deflate:{}", level),
+            CompressionKind::Gzip(level) => write!(f, "gzip:{}", level),
+        }
+    }
+}
+
+impl CompressionKind {
+    fn parse_level<T>(
+        level: Option<&str>,
+        default: T,
+        range: std::ops::RangeInclusive<T>,
+    ) -> Result<T, String>
+    where
+        T: FromStr + PartialOrd + std::fmt::Display,
+    {
+        let level = match level {
+            Some(level) => level
+                .parse()
+                .map_err(|_| format!("invalid compression level {}", level))?,
+            None => default,
+        };
+
+        if range.contains(&level) {
+            Ok(level)
+        } else {
+            Err(format!(
+                "compression level {} is not in the range {}..={}",
+                level,
+                range.start(),
+                range.end()
+            ))
+        }
+    }
+
+    /// The name of the algorithm, as it is sent to the server
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionKind::Zstd(_) => "ZSTD",
+            CompressionKind::Deflate(_) => "DEFLATE",
+            CompressionKind::Gzip(_) => "GZIP",
+        }
+    }
+
+    pub fn compression_string(&self) -> Vec<u8> {
+        format!("COMPRESS {}\n", self.name()).into_bytes()
+    }
+
+    /// The command that asks the server to decompress with the dictionary
+    /// that has ID `id`
+    pub fn dictionary_compression_string(&self, id: u32) -> Vec<u8> {
+        format!("COMPRESS {} {}\n", self.name(), id).into_bytes()
+    }
+}
+
+enum Encoder {
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+    Deflate(DeflateEncoder<Vec<u8>>),
+    Gzip(GzEncoder<Vec<u8>>),
+}
+
+/// A long-lived compression stream for a single connection.
+///
+/// All frames sent over a connection are compressed as one stream, so
+/// content that repeats between frames compresses against earlier frames.
+pub struct Compressor {
+    encoder: Encoder,
+}
+
+impl Compressor {
+    /// Create a compressor. A `dictionary` is only used by zstd.
+    pub fn new(kind: CompressionKind, dictionary: Option<&Dictionary>) -> std::io::Result<Self> {
+        let encoder = match kind {
+            CompressionKind::Zstd(level) => Encoder::Zstd(match dictionary {
+                Some(dictionary) => zstd::stream::write::Encoder::with_dictionary(
+                    Vec::new(),
+                    level,
+                    dictionary.data(),
+                )?,
+                None => zstd::stream::write::Encoder::new(Vec::new(), level)?,
+            }),
+            CompressionKind::Deflate(level) => {
+                Encoder::Deflate(DeflateEncoder::new(Vec::new(), Compression::new(level)))
+            }
+            CompressionKind::Gzip(level) => {
+                Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::new(level)))
+            }
+        };
+
+        Ok(Self { encoder })
+    }
+
+    /// Compress `data`. Returns the compressed data that the encoder
+    /// produced so far, which may not include all of `data` until the
+    /// stream is flushed.
+    pub fn write(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let output = match &mut self.encoder {
+            Encoder::Zstd(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+            Encoder::Deflate(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+        };
+
+        Ok(std::mem::take(output))
+    }
+
+    /// Flush the stream, so that the server can decompress everything that
+    /// was written without waiting for the next frame. Returns the rest of
+    /// the compressed data.
+    pub fn flush(&mut self) -> std::io::Result<Vec<u8>> {
+        let output = match &mut self.encoder {
+            Encoder::Zstd(encoder) => {
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+            Encoder::Deflate(encoder) => {
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+            Encoder::Gzip(encoder) => {
+                encoder.flush()?;
+                encoder.get_mut()
+            }
+        };
+
+        Ok(std::mem::take(output))
+    }
+
+    /// Compress `data`, and flush the stream
+    pub fn compress(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut output = self.write(data)?;
+        output.extend(self.flush()?);
+        Ok(output)
+    }
+}
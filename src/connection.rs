@@ -0,0 +1,265 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    time::Duration,
+};
+
+use crate::compression::Compressor;
+
+/// The longest line we are willing to read from a server
+const MAX_LINE_LENGTH: usize = 1024;
+
+/// A stream that a [`Connection`] can be made over
+pub trait Socket: Read + Write + Send {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+}
+
+/// Something that new sockets to a server can be opened with
+pub trait Connector {
+    type Socket: Socket;
+
+    fn connect(&self) -> std::io::Result<Self::Socket>;
+
+    /// Called after every frame was written to all connections
+    fn frame_sent(&self) {}
+}
+
+#[derive(Debug)]
+pub enum ReadLineError {
+    Io(std::io::Error),
+    /// No full line was received before the read timeout expired
+    Timeout(String),
+    /// The server closed the connection before sending a full line
+    Closed(String),
+    /// The server sent a line longer than [`MAX_LINE_LENGTH`]
+    TooLong(String),
+}
+
+impl From<std::io::Error> for ReadLineError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A socket with a buffer for line-based responses. Any data received
+/// after a line is kept around for the next read.
+pub struct Connection<T>
+where
+    T: Socket,
+{
+    pub socket: T,
+    /// Compresses everything that is sent, once compression is enabled
+    pub compressor: Option<Compressor>,
+    buffer: Vec<u8>,
+}
+
+impl<T> Connection<T>
+where
+    T: Socket,
+{
+    pub fn new(socket: T) -> Self {
+        Self {
+            socket,
+            compressor: None,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Send `data` and flush the compressed stream, so that the server
+    /// handles all of it right away. Returns the amount of bytes that were
+    /// actually sent.
+    pub fn send(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        Ok(self.write(data)? + self.flush()?)
+    }
+
+    /// Send `data`, compressing it if compression is enabled. Compressed
+    /// data may not reach the server until [`Connection::flush`] is called.
+    /// Returns the amount of bytes that were actually sent.
+    pub fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        match self.compressor.as_mut() {
+            Some(compressor) => {
+                let compressed = compressor.write(data)?;
+                self.socket.write_all(&compressed)?;
+                Ok(compressed.len())
+            }
+            None => {
+                self.socket.write_all(data)?;
+                Ok(data.len())
+            }
+        }
+    }
+
+    /// Flush the compressed stream, if compression is enabled. Returns the
+    /// amount of bytes that were sent.
+    pub fn flush(&mut self) -> std::io::Result<usize> {
+        match self.compressor.as_mut() {
+            Some(compressor) => {
+                let compressed = compressor.flush()?;
+                self.socket.write_all(&compressed)?;
+                Ok(compressed.len())
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Read a single line, without the trailing `\n` or `\r\n`.
+    pub fn read_line(&mut self) -> Result<String, ReadLineError> {
+        let mut searched = 0;
+        loop {
+            if let Some(end) = self.buffer[searched..].iter().position(|b| *b == b'\n') {
+                let end = searched + end;
+                let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(String::from_utf8_lossy(&line).into_owned());
+            }
+
+            searched = self.buffer.len();
+
+            if self.buffer.len() > MAX_LINE_LENGTH {
+                return Err(ReadLineError::TooLong(self.partial_line()));
+            }
+
+            self.fill_buffer()?;
+        }
+    }
+
+    /// Read exactly `len` bytes.
+    pub fn read_exact(&mut self, len: usize) -> Result<Vec<u8>, ReadLineError> {
+        while self.buffer.len() < len {
+            self.fill_buffer()?;
+        }
+
+        Ok(self.buffer.drain(..len).collect())
+    }
+
+    /// Read whatever data is available from the socket into the buffer.
+    fn fill_buffer(&mut self) -> Result<(), ReadLineError> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            let len = match self.socket.read(&mut chunk) {
+                Ok(0) => return Err(ReadLineError::Closed(self.partial_line())),
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Err(ReadLineError::Timeout(self.partial_line()))
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            self.buffer.extend_from_slice(&chunk[..len]);
+            return Ok(());
+        }
+    }
+
+    fn partial_line(&self) -> String {
+        String::from_utf8_lossy(&self.buffer).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// A socket that returns one of `reads` on every read, and reports that
+    /// the connection was closed once they run out
+    struct TestSocket {
+        reads: VecDeque<std::io::Result<Vec<u8>>>,
+    }
+
+    impl Read for TestSocket {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let data = match self.reads.pop_front() {
+                Some(read) => read?,
+                None => return Ok(0),
+            };
+            buf[..data.len()].copy_from_slice(&data);
+            Ok(data.len())
+        }
+    }
+
+    impl Write for TestSocket {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Socket for TestSocket {
+        fn set_read_timeout(&self, _timeout: Option<Duration>) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn connection(reads: Vec<std::io::Result<&[u8]>>) -> Connection<TestSocket> {
+        Connection::new(TestSocket {
+            reads: reads
+                .into_iter()
+                .map(|read| read.map(<[u8]>::to_vec))
+                .collect(),
+        })
+    }
+
+    #[test]
+    fn lines_split_across_reads() {
+        let mut connection = connection(vec![
+            Ok(b"SIZE 80"),
+            Ok(b"0 600\nPX 1 2 FF"),
+            Ok(b"0000\r"),
+            Ok(b"\nHELP\r\nPB"),
+        ]);
+
+        assert_eq!(connection.read_line().unwrap(), "SIZE 800 600");
+        assert_eq!(connection.read_line().unwrap(), "PX 1 2 FF0000");
+        assert_eq!(connection.read_line().unwrap(), "HELP");
+        assert_eq!(connection.read_exact(2).unwrap(), b"PB");
+    }
+
+    #[test]
+    fn interrupted_reads_are_retried() {
+        let mut connection =
+            connection(vec![Err(ErrorKind::Interrupted.into()), Ok(b"SIZE 1 1\n")]);
+
+        assert_eq!(connection.read_line().unwrap(), "SIZE 1 1");
+    }
+
+    #[test]
+    fn timeout() {
+        for kind in [ErrorKind::WouldBlock, ErrorKind::TimedOut].iter() {
+            let mut connection = connection(vec![Ok(b"SIZE 1"), Err((*kind).into())]);
+
+            match connection.read_line() {
+                Err(ReadLineError::Timeout(partial)) => assert_eq!(partial, "SIZE 1"),
+                result => panic!("Expected a timeout, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn closed() {
+        let mut connection = connection(vec![Ok(b"SIZE 1 1\nSIZE")]);
+
+        assert_eq!(connection.read_line().unwrap(), "SIZE 1 1");
+        match connection.read_line() {
+            Err(ReadLineError::Closed(partial)) => assert_eq!(partial, "SIZE"),
+            result => panic!("Expected the connection to be closed, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn too_long() {
+        let line = vec![b'A'; MAX_LINE_LENGTH + 1];
+        let mut connection = connection(vec![Ok(&line), Ok(b"\n")]);
+
+        assert!(matches!(
+            connection.read_line(),
+            Err(ReadLineError::TooLong(_))
+        ));
+    }
+}
@@ -0,0 +1,435 @@
+//! Parses the commands that a client sends back out of a byte stream. This
+//! is the inverse of [`PixelCollector`](crate::pixelcollector::PixelCollector).
+//!
+//! The decoder is incremental: bytes are fed in as they arrive, and only
+//! complete commands are returned.
+
+use std::io;
+
+use zstd::stream::raw::Operation;
+
+use crate::{color::Color, dialect::BinaryLayout, dictionary::Dictionary};
+
+/// The longest text command that is accepted
+pub const MAX_LINE_LENGTH: usize = 1024;
+
+/// A command sent by a client
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Draw a pixel. The offset is already added to the coordinates.
+    Pixel {
+        x: usize,
+        y: usize,
+        color: Color,
+    },
+    /// Ask for the color of a pixel. The offset is already added to the
+    /// coordinates.
+    Query {
+        x: usize,
+        y: usize,
+    },
+    /// Add `x` and `y` to the coordinates of all later text commands
+    Offset {
+        x: usize,
+        y: usize,
+    },
+    Size,
+    Help,
+    /// `COMPRESS` with its arguments. If the server accepts it, everything
+    /// after it is compressed, see [`Decoder::decompress`].
+    Compress(Vec<String>),
+    /// A line that is not a known command, or has invalid arguments
+    Unknown(String),
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The compressed stream is invalid
+    Io(io::Error),
+    /// A text command is longer than [`MAX_LINE_LENGTH`]
+    LineTooLong,
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(e: io::Error) -> Self {
+        DecodeError::Io(e)
+    }
+}
+
+pub struct Decoder {
+    /// The binary protocol to decode, if any
+    binary: Option<BinaryLayout>,
+    offset: (usize, usize),
+    buffer: Vec<u8>,
+    /// The start of the bytes in `buffer` that have not been decoded yet
+    position: usize,
+    decompressor: Option<zstd::stream::raw::Decoder<'static>>,
+}
+
+impl Decoder {
+    /// Create a decoder for text commands, and binary commands with the
+    /// `binary` layout if it is set
+    pub fn new(binary: Option<BinaryLayout>) -> Self {
+        Self {
+            binary,
+            offset: (0, 0),
+            buffer: Vec::new(),
+            position: 0,
+            decompressor: None,
+        }
+    }
+
+    /// The offset set by the last `OFFSET` command
+    pub fn offset(&self) -> (usize, usize) {
+        self.offset
+    }
+
+    /// Add bytes that were received
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), DecodeError> {
+        self.buffer.drain(..self.position);
+        self.position = 0;
+
+        match &mut self.decompressor {
+            Some(decompressor) => decompress(decompressor, data, &mut self.buffer)?,
+            None => self.buffer.extend_from_slice(data),
+        }
+
+        Ok(())
+    }
+
+    /// Decompress everything after the last decoded command with zstd. This
+    /// includes bytes that were already fed in.
+    pub fn decompress(&mut self, dictionary: Option<&Dictionary>) -> Result<(), DecodeError> {
+        let mut decompressor = match dictionary {
+            Some(dictionary) => zstd::stream::raw::Decoder::with_dictionary(dictionary.data())?,
+            None => zstd::stream::raw::Decoder::new()?,
+        };
+
+        let compressed = self.buffer.split_off(self.position);
+        self.buffer.clear();
+        self.position = 0;
+        decompress(&mut decompressor, &compressed, &mut self.buffer)?;
+
+        self.decompressor = Some(decompressor);
+        Ok(())
+    }
+
+    /// Decode the next command, or return `None` if it has not been fed in
+    /// completely yet. After an error, the rest of the stream can't be
+    /// decoded.
+    pub fn next_command(&mut self) -> Result<Option<Command>, DecodeError> {
+        let rest = &self.buffer[self.position..];
+
+        if self
+            .binary
+            .is_some_and(|layout| rest.starts_with(&layout.opcode))
+        {
+            if rest.len() < BinaryLayout::COMMAND_LEN {
+                return Ok(None);
+            }
+
+            let x = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+            let y = u16::from_le_bytes([rest[4], rest[5]]) as usize;
+            let color = Color::from_rgba(rest[6], rest[7], rest[8], Some(rest[9]));
+
+            self.position += BinaryLayout::COMMAND_LEN;
+            return Ok(Some(Command::Pixel { x, y, color }));
+        }
+
+        let end = match rest.iter().position(|b| *b == b'\n') {
+            Some(end) => end,
+            None if rest.len() > MAX_LINE_LENGTH => return Err(DecodeError::LineTooLong),
+            None => return Ok(None),
+        };
+
+        let line = String::from_utf8_lossy(&rest[..end]).into_owned();
+        self.position += end + 1;
+
+        Ok(Some(self.decode_line(line.trim_end_matches('\r'))))
+    }
+
+    fn decode_line(&mut self, line: &str) -> Command {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let coordinates = |x: &str, y: &str| -> Option<(usize, usize)> {
+            Some((x.parse().ok()?, y.parse().ok()?))
+        };
+        let (offset_x, offset_y) = self.offset;
+
+        let command = match parts.as_slice() {
+            ["HELP"] => Some(Command::Help),
+            ["SIZE"] => Some(Command::Size),
+            ["OFFSET", x, y] => coordinates(x, y).map(|(x, y)| {
+                self.offset = (x, y);
+                Command::Offset { x, y }
+            }),
+            ["PX", x, y] => coordinates(x, y).map(|(x, y)| Command::Query {
+                x: x + offset_x,
+                y: y + offset_y,
+            }),
+            ["PX", x, y, color] => match (coordinates(x, y), color.parse()) {
+                (Some((x, y)), Ok(color)) => Some(Command::Pixel {
+                    x: x + offset_x,
+                    y: y + offset_y,
+                    color,
+                }),
+                _ => None,
+            },
+            ["COMPRESS", arguments @ ..] => Some(Command::Compress(
+                arguments
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .collect(),
+            )),
+            _ => None,
+        };
+
+        command.unwrap_or_else(|| Command::Unknown(line.to_string()))
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = Result<Command, DecodeError>;
+
+    /// The next complete command. Iteration ends when more input is needed,
+    /// and can be resumed after feeding it in.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_command().transpose()
+    }
+}
+
+/// Decompress all of `data` and append the result to `output`
+fn decompress(
+    decompressor: &mut zstd::stream::raw::Decoder<'static>,
+    mut data: &[u8],
+    output: &mut Vec<u8>,
+) -> io::Result<()> {
+    let mut chunk = vec![0u8; 65536];
+    loop {
+        let status = decompressor.run_on_buffers(data, &mut chunk)?;
+        output.extend_from_slice(&chunk[..status.bytes_written]);
+        data = &data[status.bytes_read..];
+
+        // The decompressor only leaves room in the output once it has
+        // flushed everything it could
+        if data.is_empty() && status.bytes_written < chunk.len() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::{
+        capabilities::Capabilities,
+        codec::{CodecData, CodecOptions},
+        compression::{CompressionKind, Compressor},
+        dialect::Dialect,
+        pixelcollector::PixelCollector,
+        window::Window,
+    };
+
+    const WIDTH: usize = 300;
+    const HEIGHT: usize = 200;
+
+    fn options(dialect: Dialect, binary_px: bool) -> CodecOptions {
+        CodecOptions {
+            binary_px,
+            dialect,
+            probe_capabilities: false,
+            ..CodecOptions::default()
+        }
+    }
+
+    fn random_pixels(count: usize) -> Vec<(usize, usize, Color)> {
+        let mut rng = thread_rng();
+        (0..count)
+            .map(|_| {
+                let color = Color::from_rgba(
+                    rng.gen(),
+                    rng.gen(),
+                    rng.gen(),
+                    // Every fourth pixel is opaque, and transparent ones
+                    // are never sent
+                    Some(if rng.gen_ratio(1, 4) {
+                        0xFF
+                    } else {
+                        rng.gen_range(1..=0xFF)
+                    }),
+                );
+                (rng.gen_range(0..WIDTH), rng.gen_range(0..HEIGHT), color)
+            })
+            .collect()
+    }
+
+    /// Encode `pixels` with a pixel collector, and return the encoded
+    /// chunks of the frame
+    fn encode(options: &CodecOptions, pixels: &[(usize, usize, Color)]) -> Vec<Vec<u8>> {
+        let mut collector: PixelCollector = CodecData {
+            window: Window {
+                x_width: WIDTH,
+                y_height: HEIGHT,
+            },
+            options: options.clone(),
+            capabilities: Capabilities::unknown(),
+        }
+        .into();
+        for (x, y, color) in pixels {
+            collector.add_pixel_colored(*x as i32, *y as i32, color);
+        }
+        collector.into_frame().chunks
+    }
+
+    /// Decode the pixels in every chunk with a fresh decoder, feeding the
+    /// chunks in pieces of `piece_len` bytes
+    fn decode(
+        options: &CodecOptions,
+        chunks: &[Vec<u8>],
+        piece_len: usize,
+    ) -> Vec<(usize, usize, Color)> {
+        let binary = options.dialect.binary.filter(|_| options.binary_px);
+        let mut pixels = Vec::new();
+
+        for chunk in chunks {
+            let mut decoder = Decoder::new(binary);
+            for piece in chunk.chunks(piece_len) {
+                decoder.feed(piece).unwrap();
+                for command in &mut decoder {
+                    match command.unwrap() {
+                        Command::Pixel { x, y, color } => pixels.push((x, y, color)),
+                        Command::Offset { .. } => {}
+                        command => panic!("unexpected command {:?}", command),
+                    }
+                }
+            }
+            assert_eq!(decoder.next_command().unwrap(), None);
+        }
+
+        pixels.sort();
+        pixels
+    }
+
+    /// The pixels as the server should see them after encoding them for
+    /// `dialect`
+    fn expected(dialect: Dialect, pixels: &[(usize, usize, Color)]) -> Vec<(usize, usize, Color)> {
+        let mut expected: Vec<_> = pixels
+            .iter()
+            .map(|(x, y, color)| {
+                let mut color = *color;
+                if !dialect.alpha {
+                    color.a = Some(0xFF);
+                }
+                (*x, *y, color)
+            })
+            .collect();
+        expected.sort();
+        expected
+    }
+
+    fn assert_round_trip(options: CodecOptions) {
+        let pixels = random_pixels(2000);
+        let chunks = encode(&options, &pixels);
+        let expected = expected(options.dialect, &pixels);
+
+        assert_eq!(decode(&options, &chunks, usize::MAX), expected);
+        assert_eq!(decode(&options, &chunks, 7), expected);
+    }
+
+    #[test]
+    fn round_trip_text() {
+        for dialect in Dialect::ALL.iter() {
+            assert_round_trip(options(*dialect, false));
+        }
+    }
+
+    #[test]
+    fn round_trip_binary() {
+        for dialect in Dialect::ALL
+            .iter()
+            .filter(|dialect| dialect.binary.is_some())
+        {
+            assert_round_trip(options(*dialect, true));
+        }
+    }
+
+    #[test]
+    fn round_trip_with_offset_over_multiple_connections() {
+        assert_round_trip(CodecOptions {
+            offset: true,
+            lowercase_hex: true,
+            connections: 3,
+            ..options(Dialect::BREAKWATER, false)
+        });
+    }
+
+    #[test]
+    fn round_trip_compressed() {
+        let options = options(Dialect::GENERIC, false);
+        let pixels = random_pixels(2000);
+        let frame = encode(&options, &pixels).concat();
+
+        let mut compressor = Compressor::new(CompressionKind::Zstd(3), None).unwrap();
+        let mut stream = CompressionKind::Zstd(3).compression_string();
+        let handshake_len = stream.len();
+        // Two flushes, like two frames
+        let half = frame.len() / 2;
+        stream.extend(compressor.compress(&frame[..half]).unwrap());
+        stream.extend(compressor.compress(&frame[half..]).unwrap());
+
+        // Feed the compressed data together with the command, as it would
+        // arrive over a socket
+        let mut decoder = Decoder::new(None);
+        decoder.feed(&stream[..handshake_len + 5]).unwrap();
+        assert_eq!(
+            decoder.next_command().unwrap(),
+            Some(Command::Compress(vec!["ZSTD".to_string()]))
+        );
+        decoder.decompress(None).unwrap();
+
+        let mut decoded = Vec::new();
+        for piece in stream[handshake_len + 5..].chunks(100) {
+            decoder.feed(piece).unwrap();
+            for command in &mut decoder {
+                if let Command::Pixel { x, y, color } = command.unwrap() {
+                    decoded.push((x, y, color));
+                }
+            }
+        }
+        decoded.sort();
+
+        assert_eq!(decoded, expected(options.dialect, &pixels));
+    }
+
+    #[test]
+    fn other_commands() {
+        let mut decoder = Decoder::new(None);
+        decoder
+            .feed(b"HELP\r\nSIZE\nOFFSET 10 20\nPX 1 2\nPX 1 2 zz\nCOMPRESS ZSTD 42\n")
+            .unwrap();
+        let commands: Vec<Command> = decoder.map(Result::unwrap).collect();
+
+        assert_eq!(
+            commands,
+            [
+                Command::Help,
+                Command::Size,
+                Command::Offset { x: 10, y: 20 },
+                Command::Query { x: 11, y: 22 },
+                Command::Unknown("PX 1 2 zz".to_string()),
+                Command::Compress(vec!["ZSTD".to_string(), "42".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_lines_are_rejected() {
+        let mut decoder = Decoder::new(None);
+        decoder.feed(&[b'A'; MAX_LINE_LENGTH + 1]).unwrap();
+        assert!(matches!(
+            decoder.next_command(),
+            Err(DecodeError::LineTooLong)
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    canvas::{Canvas, Region},
+    codec::{CodecData, DataProducer, Frame, RunError},
+    color::Color,
+    decoder::{Command, Decoder},
+    dialect::BinaryLayout,
+    pixelcollector::PixelCollector,
+};
+
+/// Keeps the first frame of another data producer on the canvas.
+///
+/// Every `interval`, the pixels of the frame are read back, and only the
+/// ones that no longer have the intended color are sent. Pixels that are
+/// not fully opaque are only sent once.
+pub struct Defender<D>
+where
+    D: DataProducer,
+{
+    inner: D,
+    interval: Duration,
+    target: HashMap<(usize, usize), Color>,
+    /// The coordinates of the pixels in `target`, row by row
+    pixels: Vec<(usize, usize)>,
+    region: Option<Region>,
+    data: Frame,
+    codec_data: Option<CodecData>,
+}
+
+impl<D> Defender<D>
+where
+    D: DataProducer,
+{
+    pub fn new(inner: D, interval: Duration) -> Self {
+        Self {
+            inner,
+            interval,
+            target: HashMap::new(),
+            pixels: Vec::new(),
+            region: None,
+            data: Frame::default(),
+            codec_data: None,
+        }
+    }
+}
+
+/// Decode the pixels in `chunk`, which contains the commands for a single
+/// connection
+fn decode_chunk(
+    chunk: &[u8],
+    binary: Option<BinaryLayout>,
+    pixels: &mut HashMap<(usize, usize), Color>,
+) {
+    let mut decoder = Decoder::new(binary);
+    if let Err(e) = decoder.feed(chunk) {
+        log::warn!("Could not decode the frame to defend: {:?}", e);
+        return;
+    }
+
+    for command in decoder {
+        match command {
+            Ok(Command::Pixel { x, y, color }) => {
+                pixels.insert((x, y), color);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("Could not decode the frame to defend: {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+impl<D> DataProducer for Defender<D>
+where
+    D: DataProducer,
+{
+    fn do_setup(&mut self, codec: &CodecData) -> Result<(), String> {
+        self.inner.do_setup(codec)?;
+        let (frame, _) = self.inner.get_next_data().map_err(|e| format!("{:?}", e))?;
+
+        let binary = codec
+            .options
+            .dialect
+            .binary
+            .filter(|_| codec.options.binary_px);
+
+        let mut pixels = HashMap::new();
+        for chunk in frame.chunks.iter() {
+            decode_chunk(chunk, binary, &mut pixels);
+        }
+
+        // Pixels that are not fully opaque are blended with whatever is on the
+        // canvas, so we can't tell whether they have been overwritten
+        let mut translucent: PixelCollector = codec.clone().into();
+        pixels.retain(|(x, y), color| {
+            if color.a == Some(0xFF) {
+                true
+            } else {
+                translucent.add_pixel_colored(*x as i32, *y as i32, color);
+                false
+            }
+        });
+
+        self.region = pixels.keys().fold(None, |region: Option<Region>, (x, y)| {
+            Some(match region {
+                Some(region) => {
+                    let min_x = region.x.min(*x);
+                    let min_y = region.y.min(*y);
+                    Region {
+                        x: min_x,
+                        y: min_y,
+                        width: (region.x + region.width).max(x + 1) - min_x,
+                        height: (region.y + region.height).max(y + 1) - min_y,
+                    }
+                }
+                None => Region {
+                    x: *x,
+                    y: *y,
+                    width: 1,
+                    height: 1,
+                },
+            })
+        });
+
+        log::info!("Defending {} pixels", pixels.len());
+
+        self.pixels = pixels.keys().copied().collect();
+        self.pixels.sort_unstable_by_key(|(x, y)| (*y, *x));
+        self.target = pixels;
+        self.data = translucent.into_frame();
+        self.codec_data = Some(codec.clone());
+        Ok(())
+    }
+
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
+        Ok((std::mem::take(&mut self.data), Some(self.interval)))
+    }
+
+    fn readback_region(&self) -> Option<Region> {
+        self.region
+    }
+
+    fn readback_pixels(&self) -> Option<&[(usize, usize)]> {
+        Some(&self.pixels)
+    }
+
+    fn update_canvas(&mut self, canvas: &Canvas) {
+        let mut pixel_collector: PixelCollector = self.codec_data.clone().unwrap().into();
+
+        let mut overwritten = 0;
+        for ((x, y), color) in self.target.iter() {
+            let current = canvas.get(*x, *y);
+            let intact = current.is_some_and(|c| (c.r, c.g, c.b) == (color.r, color.g, color.b));
+
+            if !intact {
+                pixel_collector.add_pixel_colored(*x as i32, *y as i32, color);
+                overwritten += 1;
+            }
+        }
+
+        if overwritten > 0 {
+            log::debug!("Repainting {} overwritten pixels", overwritten);
+        }
+
+        self.data.append(pixel_collector.into_frame());
+    }
+}
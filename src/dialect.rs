@@ -0,0 +1,93 @@
+use std::{fmt::Display, str::FromStr};
+
+/// How pixels are encoded in the binary protocol
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BinaryLayout {
+    /// The two bytes that every command starts with
+    pub opcode: [u8; 2],
+}
+
+impl BinaryLayout {
+    /// The length of a single command: the opcode, the little endian `u16`
+    /// x and y coordinates, and the RGBA color
+    pub const COMMAND_LEN: usize = 10;
+}
+
+/// The variant of the pixelflut protocol that a server speaks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dialect {
+    pub name: &'static str,
+    /// The binary protocol, if the server has one
+    pub binary: Option<BinaryLayout>,
+    /// Whether the server blends colors with an alpha component. If not,
+    /// colors are always sent without one.
+    pub alpha: bool,
+    /// Send `WW` for opaque shades of gray
+    pub grayscale: bool,
+}
+
+const PB: BinaryLayout = BinaryLayout { opcode: *b"PB" };
+
+impl Dialect {
+    /// Works with any server. Opaque colors are sent as `RRGGBB`, which
+    /// every server accepts, but other shorthands are not used.
+    pub const GENERIC: Self = Self {
+        name: "generic",
+        binary: Some(PB),
+        alpha: true,
+        grayscale: false,
+    };
+
+    pub const BREAKWATER: Self = Self {
+        name: "breakwater",
+        binary: Some(PB),
+        alpha: true,
+        grayscale: true,
+    };
+
+    pub const SHORELINE: Self = Self {
+        name: "shoreline",
+        binary: None,
+        alpha: false,
+        grayscale: false,
+    };
+
+    pub const PIXELNUKE: Self = Self {
+        name: "pixelnuke",
+        binary: None,
+        alpha: true,
+        grayscale: true,
+    };
+
+    pub const ALL: [Self; 4] = [
+        Self::GENERIC,
+        Self::BREAKWATER,
+        Self::SHORELINE,
+        Self::PIXELNUKE,
+    ];
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|dialect| dialect.name.eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|dialect| dialect.name).collect();
+                format!(
+                    "unknown dialect {}, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Display for Dialect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
@@ -0,0 +1,159 @@
+use std::{path::Path, sync::Arc};
+
+use crate::dialect::BinaryLayout;
+
+/// The default maximum size of a trained dictionary, the same as the
+/// `zstd` command line tool uses
+pub const DEFAULT_MAX_SIZE: usize = 112_640;
+
+/// The default size of the samples that input files are split into
+pub const DEFAULT_SAMPLE_SIZE: usize = 4096;
+
+/// A zstd dictionary that is shared by the compressors of all connections
+#[derive(Debug, Clone)]
+pub struct Dictionary {
+    id: u32,
+    data: Arc<[u8]>,
+}
+
+impl Dictionary {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| format!("Could not read dictionary {}: {}", path.display(), e))?;
+
+        Self::new(data).ok_or_else(|| format!("{} is not a zstd dictionary", path.display()))
+    }
+
+    /// Create a dictionary from the contents of a trained dictionary file.
+    /// Returns `None` if `data` is not a zstd dictionary.
+    pub fn new(data: Vec<u8>) -> Option<Self> {
+        let id = zstd::zstd_safe::get_dict_id(&data)?;
+        Some(Self {
+            id,
+            data: data.into(),
+        })
+    }
+
+    /// The ID that zstd stores in the dictionary, and in every frame
+    /// compressed with it
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Split `data` into samples of roughly `sample_size` bytes, without
+/// splitting up a single pixel command. Data that starts with the opcode of
+/// `binary` is split into whole binary commands.
+fn split_samples(data: &[u8], sample_size: usize, binary: Option<BinaryLayout>) -> Vec<&[u8]> {
+    let mut samples = Vec::new();
+    let mut rest = data;
+
+    // Binary commands all have the same length, so there is no
+    // separator to split on
+    let binary = binary.is_some_and(|layout| data.starts_with(&layout.opcode));
+    let command_len = BinaryLayout::COMMAND_LEN;
+
+    while !rest.is_empty() {
+        let end = if rest.len() <= sample_size {
+            rest.len()
+        } else if binary {
+            (sample_size - sample_size % command_len)
+                .max(command_len)
+                .min(rest.len())
+        } else {
+            rest[sample_size..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map(|newline| sample_size + newline + 1)
+                .unwrap_or(rest.len())
+        };
+
+        let (sample, remaining) = rest.split_at(end);
+        samples.push(sample);
+        rest = remaining;
+    }
+
+    samples
+}
+
+/// Train a dictionary on the encoded frames in `files`. Each file is split
+/// into samples of roughly `sample_size` bytes. Binary frames are recognized
+/// by the `binary` layout of the dialect they were encoded with.
+pub fn train<P: AsRef<Path>>(
+    files: &[P],
+    sample_size: usize,
+    max_size: usize,
+    binary: Option<BinaryLayout>,
+) -> Result<Vec<u8>, String> {
+    let mut contents = Vec::with_capacity(files.len());
+    for file in files {
+        let file = file.as_ref();
+        let data = std::fs::read(file)
+            .map_err(|e| format!("Could not read sample {}: {}", file.display(), e))?;
+        contents.push(data);
+    }
+
+    let samples: Vec<&[u8]> = contents
+        .iter()
+        .flat_map(|data| split_samples(data, sample_size.max(1), binary))
+        .collect();
+
+    log::info!(
+        "Training dictionary on {} samples ({} bytes)",
+        samples.len(),
+        samples.iter().map(|s| s.len()).sum::<usize>()
+    );
+
+    zstd::dict::from_samples(&samples, max_size)
+        .map_err(|e| format!("Could not train dictionary: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Dialect;
+
+    fn binary_commands(layout: BinaryLayout, count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|i| {
+                let mut command = layout.opcode.to_vec();
+                command.resize(BinaryLayout::COMMAND_LEN, i as u8);
+                command
+            })
+            .collect()
+    }
+
+    #[test]
+    fn binary_samples_are_whole_commands() {
+        let layout = Dialect::GENERIC.binary.unwrap();
+        let data = binary_commands(layout, 1000);
+
+        for sample_size in [1, 8, 9, 10, 64, 4096] {
+            let samples = split_samples(&data, sample_size, Some(layout));
+            assert_eq!(samples.concat(), data);
+            for sample in samples {
+                assert_eq!(sample.len() % BinaryLayout::COMMAND_LEN, 0);
+                assert!(sample.starts_with(&layout.opcode));
+            }
+        }
+    }
+
+    #[test]
+    fn text_samples_end_at_newlines() {
+        let data: Vec<u8> = (0..1000)
+            .flat_map(|i| format!("PX {} {} FF00AA\n", i, i * 7).into_bytes())
+            .collect();
+
+        let samples = split_samples(&data, 64, Dialect::GENERIC.binary);
+        assert_eq!(samples.concat(), data);
+        for sample in samples {
+            assert!(sample.starts_with(b"PX "));
+            assert!(sample.ends_with(b"\n"));
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Runs a codec against an in-memory canvas instead of a server, and saves
+//! the result as an image.
+
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    connection::{Connector, Socket},
+    decoder::DecodeError,
+    mockserver::{Framebuffer, Session},
+};
+
+/// The amount of frames that a dry run of a data producer that never
+/// finishes stops after, unless a maximum is given
+pub const DEFAULT_MAX_FRAMES: u64 = 100;
+
+/// The smallest delay between two frames of a GIF
+const GIF_DELAY_RESOLUTION: Duration = Duration::from_millis(10);
+
+/// How hard to try to find the best palette for every frame of a GIF, from
+/// 1 (best) to 30 (fastest)
+const GIF_QUANTIZATION_SPEED: i32 = 10;
+
+/// A socket that sends everything to a [`Session`] on the in-memory canvas
+pub struct DryRunSocket {
+    session: Session,
+    replies: Vec<u8>,
+}
+
+impl Write for DryRunSocket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.session.receive(buf);
+        self.replies.extend(self.session.take_replies());
+
+        match result {
+            Ok(()) => Ok(buf.len()),
+            Err(DecodeError::Io(e)) => Err(e),
+            Err(e) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?}", e),
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for DryRunSocket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Commands are handled as soon as they are written, so if there is
+        // no reply now, there never will be
+        if self.replies.is_empty() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        let len = buf.len().min(self.replies.len());
+        buf[..len].copy_from_slice(&self.replies[..len]);
+        self.replies.drain(..len);
+        Ok(len)
+    }
+}
+
+impl Socket for DryRunSocket {
+    fn set_read_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The frames of the animation that are written to a GIF
+struct Animation {
+    encoder: gif::Encoder<File>,
+    /// The last frame, which is written once it is known how long it is shown
+    pending: Option<(Duration, Framebuffer)>,
+    started: Instant,
+    /// Time spent encoding frames, which is not counted in the delays, so
+    /// that they match the timing of the data producer
+    encoding_time: Duration,
+    result: Result<(), String>,
+}
+
+impl Animation {
+    fn create(path: &Path, width: usize, height: usize) -> Result<Self, String> {
+        let error =
+            |e: &dyn std::fmt::Display| format!("Could not write {}: {}", path.display(), e);
+
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => {
+                return Err(error(&format!(
+                    "A GIF can't be {}x{} pixels",
+                    width, height
+                )))
+            }
+        };
+
+        let file = File::create(path).map_err(|e| error(&e))?;
+        let mut encoder = gif::Encoder::new(file, width, height, &[]).map_err(|e| error(&e))?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| error(&e))?;
+
+        Ok(Self {
+            encoder,
+            pending: None,
+            started: Instant::now(),
+            encoding_time: Duration::ZERO,
+            result: Ok(()),
+        })
+    }
+
+    fn now(&self) -> Duration {
+        self.started.elapsed().saturating_sub(self.encoding_time)
+    }
+
+    /// Add the current state of the canvas. Frames that are shown for less
+    /// than the delay resolution of GIFs are merged into the next frame.
+    fn add(&mut self, framebuffer: Framebuffer) {
+        let now = self.now();
+
+        self.pending = match self.pending.take() {
+            Some((shown_at, _)) if now - shown_at < GIF_DELAY_RESOLUTION => {
+                Some((shown_at, framebuffer))
+            }
+            Some((shown_at, previous)) => {
+                self.write(&previous, shown_at, now);
+                Some((now, framebuffer))
+            }
+            None => Some((now, framebuffer)),
+        };
+    }
+
+    /// Write a frame that is shown from `shown_at` until `hidden_at`
+    fn write(&mut self, framebuffer: &Framebuffer, shown_at: Duration, hidden_at: Duration) {
+        if self.result.is_err() {
+            return;
+        }
+
+        let start = Instant::now();
+
+        let mut pixels = framebuffer.to_image().into_raw();
+        let mut frame = gif::Frame::from_rgba_speed(
+            framebuffer.width() as u16,
+            framebuffer.height() as u16,
+            &mut pixels,
+            GIF_QUANTIZATION_SPEED,
+        );
+        // Convert the times to ticks rather than the delay, so that rounding
+        // errors don't add up over many frames
+        let ticks = |time: Duration| time.as_millis() / GIF_DELAY_RESOLUTION.as_millis();
+        frame.delay = (ticks(hidden_at) - ticks(shown_at)).min(u16::MAX as u128) as u16;
+        self.result = self
+            .encoder
+            .write_frame(&frame)
+            .map_err(|e| format!("Could not write a GIF frame: {}", e));
+
+        self.encoding_time += start.elapsed();
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        if let Some((shown_at, framebuffer)) = self.pending.take() {
+            let now = self.now();
+            self.write(&framebuffer, shown_at, now);
+        }
+        self.result
+    }
+}
+
+/// Connects to an in-memory canvas. When the codec is done, the canvas is
+/// saved with [`DryRun::finish`]: as a GIF of every frame if the output
+/// file ends with `.gif`, and as an image of the last frame otherwise.
+#[derive(Clone)]
+pub struct DryRun {
+    framebuffer: Arc<Mutex<Framebuffer>>,
+    output: PathBuf,
+    animation: Option<Arc<Mutex<Animation>>>,
+}
+
+impl DryRun {
+    pub fn new(width: usize, height: usize, output: PathBuf) -> Result<Self, String> {
+        let is_gif = output
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+
+        let animation = if is_gif {
+            Some(Arc::new(Mutex::new(Animation::create(
+                &output, width, height,
+            )?)))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            framebuffer: Arc::new(Mutex::new(Framebuffer::new(width, height))),
+            output,
+            animation,
+        })
+    }
+
+    /// Save the canvas
+    pub fn finish(self) -> Result<(), String> {
+        if let Some(animation) = self.animation {
+            let animation = match Arc::try_unwrap(animation) {
+                Ok(animation) => animation.into_inner().unwrap(),
+                Err(_) => return Err("The codec is still running".to_string()),
+            };
+            return animation.finish();
+        }
+
+        self.framebuffer
+            .lock()
+            .unwrap()
+            .to_image()
+            .save(&self.output)
+            .map_err(|e| format!("Could not save {}: {}", self.output.display(), e))
+    }
+}
+
+impl Connector for DryRun {
+    type Socket = DryRunSocket;
+
+    fn connect(&self) -> io::Result<Self::Socket> {
+        Ok(DryRunSocket {
+            session: Session::new(self.framebuffer.clone()),
+            replies: Vec::new(),
+        })
+    }
+
+    fn frame_sent(&self) {
+        if let Some(animation) = &self.animation {
+            let framebuffer = self.framebuffer.lock().unwrap().clone();
+            animation.lock().unwrap().add(framebuffer);
+        }
+    }
+}
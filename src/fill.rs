@@ -3,14 +3,14 @@ use std::time::Duration;
 use rand::{prelude::SliceRandom, thread_rng};
 
 use crate::{
-    codec::{CodecData, DataProducer, RunError},
+    codec::{CodecData, DataProducer, Frame, RunError},
     color::Color,
     pixelcollector::PixelCollector,
 };
 
 pub struct Fill {
     color: Color,
-    data: Vec<u8>,
+    data: Frame,
     noisy: bool,
 }
 
@@ -18,7 +18,7 @@ impl Fill {
     pub fn new(color: Color, noisy: bool) -> Self {
         Self {
             color,
-            data: Vec::new(),
+            data: Frame::default(),
             noisy,
         }
     }
@@ -26,7 +26,6 @@ impl Fill {
 
 impl DataProducer for Fill {
     fn do_setup(&mut self, codec: &CodecData) -> Result<(), String> {
-        self.data.clear();
         let x = codec.window.get_x() as u16;
         let y = codec.window.get_y() as u16;
 
@@ -47,12 +46,12 @@ impl DataProducer for Fill {
             pixel_collector.add_pixel_colored(x as i32, y as i32, &self.color);
         }
 
-        self.data = pixel_collector.into_bytes().1;
+        self.data = pixel_collector.into_frame();
 
         Ok(())
     }
 
-    fn get_next_data(&mut self) -> Result<(Vec<u8>, Option<Duration>), RunError> {
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
         Ok((self.data.clone(), None))
     }
 }
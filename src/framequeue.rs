@@ -0,0 +1,201 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    str::FromStr,
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::codec::Frame;
+
+/// What to do with a new frame when the queue is full
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueuePolicy {
+    /// Wait until the sender took a frame out of the queue
+    Block,
+    /// Drop the oldest frame in the queue, so that the sender always gets
+    /// the most recent frames
+    DropOldest,
+}
+
+impl FromStr for QueuePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "block" => Ok(Self::Block),
+            "drop-oldest" => Ok(Self::DropOldest),
+            _ => Err(format!(
+                "unknown queue policy {}, expected block or drop-oldest",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for QueuePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Block => f.write_str("block"),
+            Self::DropOldest => f.write_str("drop-oldest"),
+        }
+    }
+}
+
+/// A frame, and how long to wait before sending the next one
+pub type QueuedFrame = (Frame, Option<Duration>);
+
+struct State {
+    frames: VecDeque<QueuedFrame>,
+    closed: bool,
+    /// Frames dropped since the last call to [`FrameQueue::take_dropped`]
+    dropped: u64,
+}
+
+/// A bounded queue of encoded frames, between the thread that produces
+/// them and the thread that sends them
+pub struct FrameQueue {
+    state: Mutex<State>,
+    changed: Condvar,
+    depth: usize,
+    policy: QueuePolicy,
+}
+
+impl FrameQueue {
+    pub fn new(depth: usize, policy: QueuePolicy) -> Self {
+        Self {
+            state: Mutex::new(State {
+                frames: VecDeque::with_capacity(depth),
+                closed: false,
+                dropped: 0,
+            }),
+            changed: Condvar::new(),
+            depth: depth.max(1),
+            policy,
+        }
+    }
+
+    /// Add a frame to the queue, applying the policy if it is full. Returns
+    /// `false` if the queue was closed, and the frame will never be sent.
+    pub fn push(&self, frame: QueuedFrame) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if self.policy == QueuePolicy::Block {
+            while state.frames.len() >= self.depth && !state.closed {
+                state = self.changed.wait(state).unwrap();
+            }
+        }
+
+        if state.closed {
+            return false;
+        }
+
+        if state.frames.len() >= self.depth {
+            state.frames.pop_front();
+            state.dropped += 1;
+        }
+
+        state.frames.push_back(frame);
+        self.changed.notify_all();
+        true
+    }
+
+    /// Take the oldest frame out of the queue, waiting for one if it is
+    /// empty. Returns `None` once the queue is closed and empty.
+    pub fn pop(&self) -> Option<QueuedFrame> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                self.changed.notify_all();
+                return Some(frame);
+            }
+
+            if state.closed {
+                return None;
+            }
+
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Stop accepting frames. The frames that are already in the queue can
+    /// still be taken out.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+
+    /// The amount of frames that were dropped since the last call
+    pub fn take_dropped(&self) -> u64 {
+        std::mem::take(&mut self.state.lock().unwrap().dropped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(pixels: usize) -> QueuedFrame {
+        (
+            Frame {
+                chunks: Vec::new(),
+                pixels,
+            },
+            Some(Duration::ZERO),
+        )
+    }
+
+    fn pixels(queue: &FrameQueue) -> Vec<usize> {
+        queue.close();
+        std::iter::from_fn(|| queue.pop())
+            .map(|(frame, _)| frame.pixels)
+            .collect()
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let queue = FrameQueue::new(2, QueuePolicy::DropOldest);
+        for pixels in 0..5 {
+            assert!(queue.push(frame(pixels)));
+        }
+
+        assert_eq!(queue.take_dropped(), 3);
+        assert_eq!(pixels(&queue), [3, 4]);
+    }
+
+    #[test]
+    fn block_waits_for_the_sender() {
+        let queue = FrameQueue::new(2, QueuePolicy::Block);
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for pixels in 0..5 {
+                    assert!(queue.push(frame(pixels)));
+                }
+                queue.close();
+            });
+
+            let received: Vec<usize> = std::iter::from_fn(|| queue.pop())
+                .map(|(frame, _)| frame.pixels)
+                .collect();
+            assert_eq!(received, [0, 1, 2, 3, 4]);
+        });
+
+        assert_eq!(queue.take_dropped(), 0);
+    }
+
+    #[test]
+    fn closing_unblocks_the_producer() {
+        let queue = FrameQueue::new(1, QueuePolicy::Block);
+        assert!(queue.push(frame(0)));
+
+        std::thread::scope(|scope| {
+            let producer = scope.spawn(|| queue.push(frame(1)));
+            queue.close();
+            assert!(!producer.join().unwrap());
+        });
+
+        assert_eq!(pixels(&queue), [0]);
+    }
+}
@@ -4,7 +4,7 @@ use std::{
 };
 
 use crate::{
-    codec::{CodecData, DataProducer},
+    codec::{CodecData, DataProducer, Frame},
     color::Color,
     pixelcollector::{PixOffset, PixelCollector},
 };
@@ -14,7 +14,7 @@ pub struct Gif {
     height_offset: i32,
     width_offset: i32,
     path: PathBuf,
-    frames: Vec<Vec<u8>>,
+    frames: Vec<Frame>,
     frame_num: usize,
 }
 
@@ -39,10 +39,10 @@ impl DataProducer for Gif {
 
         let mut decoder = decoder_opts.read_info(file).unwrap();
         log::info!("Reading all frames");
+        self.frames.clear();
 
         let start_time = Instant::now();
 
-        let mut uncompressed_bytes = 0;
         let mut out_bytes = 0;
         let mut frame_num = 0;
 
@@ -89,25 +89,14 @@ impl DataProducer for Gif {
                 }
             }
 
-            let (actual_size, frame) = pixel_collector.into_bytes();
-            let len = frame.len();
+            let frame = pixel_collector.into_frame();
             out_bytes += frame.len();
-            uncompressed_bytes += actual_size;
+            log::debug!("Finished frame {}. Size: {}", frame_num, frame.len());
             self.frames.push(frame);
-            log::debug!(
-                "Finished frame {}. Ratio: {:.02}",
-                frame_num,
-                (actual_size as f64) / (len as f64)
-            );
             frame_num += 1;
         }
 
-        log::info!(
-            "Bytes read: {}, Bytes out: {}, ratio: {:.02}",
-            uncompressed_bytes,
-            out_bytes,
-            (uncompressed_bytes as f64) / (out_bytes as f64)
-        );
+        log::info!("Bytes out: {}", out_bytes);
 
         log::info!(
             "Loaded {} frames in  {} ms",
@@ -118,7 +107,7 @@ impl DataProducer for Gif {
         Ok(())
     }
 
-    fn get_next_data(&mut self) -> Result<(Vec<u8>, Option<Duration>), crate::codec::RunError> {
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), crate::codec::RunError> {
         self.frame_num = (self.frame_num + 1) % self.frames.len();
         let frame = &self.frames[self.frame_num];
         Ok((frame.clone(), Some(self.frame_time)))
@@ -3,12 +3,12 @@ use std::{path::PathBuf, time::Duration};
 use image::{io::Reader, Pixel};
 
 use crate::{
-    codec::DataProducer,
+    codec::{DataProducer, Frame},
     pixelcollector::{PixOffset, PixelCollector},
 };
 
 pub struct Image {
-    data: Vec<u8>,
+    data: Frame,
     path: PathBuf,
     width_offset: i32,
     height_offset: i32,
@@ -23,7 +23,7 @@ impl Image {
         height_offset: i32,
     ) -> Self {
         Self {
-            data: Vec::new(),
+            data: Frame::default(),
             path,
             frame_interval,
             width_offset,
@@ -55,11 +55,11 @@ impl DataProducer for Image {
             pixelcollector.add_pixel_colored(x, y, &color);
         }
 
-        self.data = pixelcollector.into_bytes().1;
+        self.data = pixelcollector.into_frame();
         Ok(())
     }
 
-    fn get_next_data(&mut self) -> Result<(Vec<u8>, Option<Duration>), crate::codec::RunError> {
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), crate::codec::RunError> {
         Ok((self.data.clone(), self.frame_interval))
     }
 }
@@ -0,0 +1,252 @@
+use std::{collections::HashMap, convert::TryFrom};
+
+use super::font::{BitmapFont, FontError, Glyph};
+
+/// The largest width or height of a glyph that we accept, so that a broken
+/// bounding box can't make us allocate huge bitmaps
+const MAX_GLYPH_SIZE: usize = 1024;
+
+struct BoundingBox {
+    width: usize,
+    height: usize,
+    x_offset: i32,
+    y_offset: i32,
+}
+
+impl BoundingBox {
+    fn parse(args: &[&str]) -> Result<Self, FontError> {
+        if args.len() < 4 {
+            return Err(FontError::Invalid(format!(
+                "Bounding box has {} values, expected 4",
+                args.len()
+            )));
+        }
+
+        let bbx = Self {
+            width: parse_num(args[0])?,
+            height: parse_num(args[1])?,
+            x_offset: parse_num(args[2])?,
+            y_offset: parse_num(args[3])?,
+        };
+
+        if bbx.width > MAX_GLYPH_SIZE || bbx.height > MAX_GLYPH_SIZE {
+            return Err(FontError::Invalid(format!(
+                "Bounding box of {}x{} is too large",
+                bbx.width, bbx.height
+            )));
+        }
+
+        Ok(bbx)
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(value: &str) -> Result<T, FontError> {
+    value
+        .parse()
+        .map_err(|_| FontError::Invalid(format!("Invalid number {}", value)))
+}
+
+#[derive(Default)]
+struct GlyphBuilder {
+    encoding: Option<u32>,
+    advance: Option<usize>,
+    bbx: Option<BoundingBox>,
+    rows: Vec<String>,
+}
+
+/// Parse a font in the Glyph Bitmap Distribution Format.
+pub fn parse(data: &[u8]) -> Result<BitmapFont, FontError> {
+    let text = String::from_utf8_lossy(data);
+
+    let mut font_bbx = None;
+    let mut ascent = None;
+    let mut descent = None;
+    let mut glyph: Option<GlyphBuilder> = None;
+    let mut in_bitmap = false;
+
+    let mut built = Vec::new();
+
+    for line in text.lines() {
+        let mut parts = line.split_whitespace();
+        let keyword = match parts.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = parts.collect();
+
+        if let Some(current) = glyph.as_mut() {
+            if keyword == "ENDCHAR" {
+                built.push(glyph.take().unwrap());
+                in_bitmap = false;
+            } else if in_bitmap {
+                current.rows.push(keyword.to_string());
+            } else {
+                match keyword {
+                    "ENCODING" => {
+                        current.encoding = args
+                            .first()
+                            .and_then(|e| e.parse::<i64>().ok())
+                            .and_then(|e| u32::try_from(e).ok())
+                    }
+                    "DWIDTH" => current.advance = args.first().map(|a| parse_num(a)).transpose()?,
+                    "BBX" => current.bbx = Some(BoundingBox::parse(&args)?),
+                    "BITMAP" => in_bitmap = true,
+                    _ => {}
+                }
+            }
+            continue;
+        }
+
+        match keyword {
+            "FONTBOUNDINGBOX" => font_bbx = Some(BoundingBox::parse(&args)?),
+            "FONT_ASCENT" => ascent = args.first().map(|a| parse_num::<i32>(a)).transpose()?,
+            "FONT_DESCENT" => descent = args.first().map(|a| parse_num::<i32>(a)).transpose()?,
+            "STARTCHAR" => glyph = Some(GlyphBuilder::default()),
+            _ => {}
+        }
+    }
+
+    let font_bbx =
+        font_bbx.ok_or_else(|| FontError::Invalid(String::from("Missing FONTBOUNDINGBOX")))?;
+
+    let ascent = ascent.unwrap_or(font_bbx.height as i32 + font_bbx.y_offset);
+    let descent = descent.unwrap_or(-font_bbx.y_offset);
+
+    let mut glyphs = HashMap::new();
+    for builder in built {
+        let c = match builder.encoding.and_then(char::from_u32) {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let bbx = builder.bbx.as_ref().unwrap_or(&font_bbx);
+
+        let mut bitmap = Vec::with_capacity(bbx.width * bbx.height);
+        for row in builder.rows.iter().take(bbx.height) {
+            let bits = hex_bits(row)?;
+            for x in 0..bbx.width {
+                bitmap.push(*bits.get(x).unwrap_or(&0));
+            }
+        }
+        bitmap.resize(bbx.width * bbx.height, 0);
+
+        glyphs.insert(
+            c,
+            Glyph {
+                width: bbx.width,
+                height: bbx.height,
+                left: bbx.x_offset,
+                top: ascent - (bbx.y_offset + bbx.height as i32),
+                advance: builder.advance.unwrap_or(font_bbx.width),
+                bitmap,
+            },
+        );
+    }
+
+    let height = (ascent + descent).max(0) as usize;
+    let mut unknown = Glyph::block(font_bbx.width, font_bbx.height);
+    unknown.top = ascent - (font_bbx.y_offset + font_bbx.height as i32);
+
+    Ok(BitmapFont::new(glyphs, unknown, height))
+}
+
+/// Expand a row of hexadecimal bitmap data into one coverage value per
+/// pixel, most significant bit first.
+fn hex_bits(row: &str) -> Result<Vec<u8>, FontError> {
+    let mut bits = Vec::with_capacity(row.len() * 4);
+    for c in row.chars() {
+        let value = c
+            .to_digit(16)
+            .ok_or_else(|| FontError::Invalid(format!("Invalid bitmap row {}", row)))?;
+
+        for bit in (0..4).rev() {
+            bits.push(((value >> bit) & 1) as u8 * 0xFF);
+        }
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::letters::Font;
+
+    const FONT: &str = "STARTFONT 2.1
+FONT -test-
+SIZE 8 75 75
+FONTBOUNDINGBOX 4 4 0 -1
+STARTPROPERTIES 2
+FONT_ASCENT 3
+FONT_DESCENT 1
+ENDPROPERTIES
+CHARS 2
+STARTCHAR A
+ENCODING 65
+SWIDTH 500 0
+DWIDTH 5 0
+BBX 3 2 1 0
+BITMAP
+A0
+40
+ENDCHAR
+STARTCHAR unencoded
+ENCODING -1
+BITMAP
+F0
+ENDCHAR
+ENDFONT
+";
+
+    fn parse_str(font: &str) -> Result<Font, FontError> {
+        parse(font.as_bytes()).map(Font::Bitmap)
+    }
+
+    fn is_invalid(result: Result<Font, FontError>) -> bool {
+        matches!(result, Err(FontError::Invalid(_)))
+    }
+
+    #[test]
+    fn glyphs() {
+        let font = parse_str(FONT).unwrap();
+        assert_eq!(font.height(), 4);
+
+        let glyph = font.glyph('A');
+        assert_eq!(
+            (
+                glyph.width,
+                glyph.height,
+                glyph.left,
+                glyph.top,
+                glyph.advance
+            ),
+            (3, 2, 1, 1, 5)
+        );
+        assert_eq!(glyph.bitmap, [0xFF, 0, 0xFF, 0, 0xFF, 0]);
+
+        // Characters without a glyph are drawn as the font's bounding box
+        let unknown = font.glyph('B');
+        assert_eq!((unknown.width, unknown.height, unknown.top), (4, 4, 0));
+    }
+
+    #[test]
+    fn truncated() {
+        let end = FONT.find("40\n").unwrap();
+        let font = parse_str(&FONT[..end]).unwrap();
+        // The glyph that was cut off is skipped
+        assert_eq!(font.glyph('A').width, 4);
+
+        assert!(is_invalid(parse_str("STARTFONT 2.1\n")));
+    }
+
+    #[test]
+    fn malformed() {
+        let replace = |from: &str, to: &str| parse_str(&FONT.replace(from, to));
+
+        assert!(is_invalid(replace("FONTBOUNDINGBOX 4 4 0 -1", "")));
+        assert!(is_invalid(replace("BBX 3 2 1 0", "BBX 3 2")));
+        assert!(is_invalid(replace("BBX 3 2 1 0", "BBX -3 2 1 0")));
+        assert!(is_invalid(replace("BBX 3 2 1 0", "BBX 100000 100000 0 0")));
+        assert!(is_invalid(replace("DWIDTH 5 0", "DWIDTH five 0")));
+        assert!(is_invalid(replace("A0\n", "ZZ\n")));
+    }
+}
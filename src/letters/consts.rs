@@ -93,4 +93,574 @@ pub const L: Letter = [
     1, 0, 0, 0, 0,
     1, 0, 0, 0, 0,
     1, 1, 1, 1, 1
-];
\ No newline at end of file
+];
+
+pub const F: Letter = [
+    0, 1, 1, 1, 1,
+    1, 0, 0, 0, 0,
+    1, 0, 0, 0, 0,
+    1, 1, 1, 1, 0,
+    1, 0, 0, 0, 0,
+    1, 0, 0, 0, 0,
+    1, 0, 0, 0, 0
+];
+
+pub const I: Letter = [
+    0, 1, 1, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 1, 1, 0
+];
+
+pub const J: Letter = [
+    0, 0, 1, 1, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0,
+    1, 0, 0, 1, 0,
+    0, 1, 1, 0, 0
+];
+
+pub const K: Letter = [
+    1, 0, 0, 0, 1,
+    1, 0, 0, 1, 0,
+    1, 0, 1, 0, 0,
+    1, 1, 0, 0, 0,
+    1, 0, 1, 0, 0,
+    1, 0, 0, 1, 0,
+    1, 0, 0, 0, 1
+];
+
+pub const O: Letter = [
+    0, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 1, 1, 0
+];
+
+pub const P: Letter = [
+    1, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 1, 1, 1, 0,
+    1, 0, 0, 0, 0,
+    1, 0, 0, 0, 0,
+    1, 0, 0, 0, 0
+];
+
+pub const Q: Letter = [
+    0, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 1, 0, 1,
+    1, 0, 0, 1, 0,
+    0, 1, 1, 0, 1
+];
+
+pub const R: Letter = [
+    1, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 1, 1, 1, 0,
+    1, 0, 1, 0, 0,
+    1, 0, 0, 1, 0,
+    1, 0, 0, 0, 1
+];
+
+pub const S: Letter = [
+    0, 1, 1, 1, 1,
+    1, 0, 0, 0, 0,
+    1, 0, 0, 0, 0,
+    0, 1, 1, 1, 0,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 0, 1,
+    1, 1, 1, 1, 0
+];
+
+pub const T: Letter = [
+    1, 1, 1, 1, 1,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0
+];
+
+pub const V: Letter = [
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 0, 1, 0,
+    0, 0, 1, 0, 0
+];
+
+pub const W: Letter = [
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    1, 0, 1, 0, 1,
+    1, 0, 1, 0, 1,
+    1, 0, 1, 0, 1,
+    0, 1, 0, 1, 0
+];
+
+pub const X: Letter = [
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1
+];
+
+pub const Y: Letter = [
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0
+];
+
+pub const Z: Letter = [
+    1, 1, 1, 1, 1,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0,
+    1, 0, 0, 0, 0,
+    1, 1, 1, 1, 1
+];
+
+pub const ZERO: Letter = [
+    0, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 1, 1,
+    1, 0, 1, 0, 1,
+    1, 1, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 1, 1, 0
+];
+
+pub const ONE: Letter = [
+    0, 0, 1, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 1, 1, 0
+];
+
+pub const TWO: Letter = [
+    0, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0,
+    1, 1, 1, 1, 1
+];
+
+pub const THREE: Letter = [
+    1, 1, 1, 1, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 1, 1, 0
+];
+
+pub const FOUR: Letter = [
+    0, 0, 0, 1, 0,
+    0, 0, 1, 1, 0,
+    0, 1, 0, 1, 0,
+    1, 0, 0, 1, 0,
+    1, 1, 1, 1, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0
+];
+
+pub const FIVE: Letter = [
+    1, 1, 1, 1, 1,
+    1, 0, 0, 0, 0,
+    1, 1, 1, 1, 0,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 1, 1, 0
+];
+
+pub const SIX: Letter = [
+    0, 0, 1, 1, 0,
+    0, 1, 0, 0, 0,
+    1, 0, 0, 0, 0,
+    1, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 1, 1, 0
+];
+
+pub const SEVEN: Letter = [
+    1, 1, 1, 1, 1,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 1, 0, 0, 0
+];
+
+pub const EIGHT: Letter = [
+    0, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 1, 1, 0
+];
+
+pub const NINE: Letter = [
+    0, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    1, 0, 0, 0, 1,
+    0, 1, 1, 1, 1,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 1, 0,
+    0, 1, 1, 0, 0
+];
+
+pub const EXCLAMATION: Letter = [
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 1, 0, 0
+];
+
+pub const QUOTE: Letter = [
+    0, 1, 0, 1, 0,
+    0, 1, 0, 1, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const HASH: Letter = [
+    0, 1, 0, 1, 0,
+    0, 1, 0, 1, 0,
+    1, 1, 1, 1, 1,
+    0, 1, 0, 1, 0,
+    1, 1, 1, 1, 1,
+    0, 1, 0, 1, 0,
+    0, 1, 0, 1, 0
+];
+
+pub const DOLLAR: Letter = [
+    0, 0, 1, 0, 0,
+    0, 1, 1, 1, 1,
+    1, 0, 1, 0, 0,
+    0, 1, 1, 1, 0,
+    0, 0, 1, 0, 1,
+    1, 1, 1, 1, 0,
+    0, 0, 1, 0, 0
+];
+
+pub const PERCENT: Letter = [
+    1, 1, 0, 0, 0,
+    1, 1, 0, 0, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0,
+    1, 0, 0, 1, 1,
+    0, 0, 0, 1, 1
+];
+
+pub const AMPERSAND: Letter = [
+    0, 1, 1, 0, 0,
+    1, 0, 0, 1, 0,
+    1, 0, 1, 0, 0,
+    0, 1, 0, 0, 0,
+    1, 0, 1, 0, 1,
+    1, 0, 0, 1, 0,
+    0, 1, 1, 0, 1
+];
+
+pub const APOSTROPHE: Letter = [
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const LEFT_PAREN: Letter = [
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 1, 0
+];
+
+pub const RIGHT_PAREN: Letter = [
+    0, 1, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0
+];
+
+pub const ASTERISK: Letter = [
+    0, 0, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    1, 0, 1, 0, 1,
+    0, 1, 1, 1, 0,
+    1, 0, 1, 0, 1,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const PLUS: Letter = [
+    0, 0, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    1, 1, 1, 1, 1,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const COMMA: Letter = [
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0
+];
+
+pub const MINUS: Letter = [
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    1, 1, 1, 1, 1,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const PERIOD: Letter = [
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 1, 1, 0, 0
+];
+
+pub const SLASH: Letter = [
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0,
+    1, 0, 0, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const COLON: Letter = [
+    0, 0, 0, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const SEMICOLON: Letter = [
+    0, 0, 0, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0
+];
+
+pub const LESS_THAN: Letter = [
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0,
+    1, 0, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 1, 0
+];
+
+pub const EQUALS: Letter = [
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    1, 1, 1, 1, 1,
+    0, 0, 0, 0, 0,
+    1, 1, 1, 1, 1,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const GREATER_THAN: Letter = [
+    0, 1, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0
+];
+
+pub const QUESTION: Letter = [
+    0, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 1, 0, 0
+];
+
+pub const AT: Letter = [
+    0, 1, 1, 1, 0,
+    1, 0, 0, 0, 1,
+    0, 0, 0, 0, 1,
+    0, 1, 1, 0, 1,
+    1, 0, 1, 0, 1,
+    1, 0, 1, 0, 1,
+    0, 1, 1, 1, 0
+];
+
+pub const LEFT_BRACKET: Letter = [
+    0, 1, 1, 1, 0,
+    0, 1, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 1, 1, 1, 0
+];
+
+pub const BACKSLASH: Letter = [
+    0, 0, 0, 0, 0,
+    1, 0, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 0, 1,
+    0, 0, 0, 0, 0
+];
+
+pub const RIGHT_BRACKET: Letter = [
+    0, 1, 1, 1, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 1, 0,
+    0, 1, 1, 1, 0
+];
+
+pub const CARET: Letter = [
+    0, 0, 1, 0, 0,
+    0, 1, 0, 1, 0,
+    1, 0, 0, 0, 1,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const UNDERSCORE: Letter = [
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    1, 1, 1, 1, 1
+];
+
+pub const BACKTICK: Letter = [
+    0, 1, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0
+];
+
+pub const LEFT_BRACE: Letter = [
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 1, 0
+];
+
+pub const PIPE: Letter = [
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0
+];
+
+pub const RIGHT_BRACE: Letter = [
+    0, 1, 0, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 0, 1, 0,
+    0, 0, 1, 0, 0,
+    0, 0, 1, 0, 0,
+    0, 1, 0, 0, 0
+];
+
+pub const TILDE: Letter = [
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+    0, 1, 0, 0, 0,
+    1, 0, 1, 0, 1,
+    0, 0, 0, 1, 0,
+    0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0
+];
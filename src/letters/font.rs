@@ -0,0 +1,141 @@
+use std::{borrow::Cow, collections::HashMap, path::Path};
+
+use super::{bdf, psf, truetype, Letter, LETTER_HEIGHT, LETTER_WIDTH, UNKNOWN};
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(std::io::Error),
+    UnknownFormat,
+    Invalid(String),
+}
+
+impl From<std::io::Error> for FontError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A single glyph, stored as a coverage value (0 to 255) per pixel.
+///
+/// `left` and `top` are the offset of the top-left corner of the bitmap
+/// relative to the pen position at the top of the line.
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    pub width: usize,
+    pub height: usize,
+    pub left: i32,
+    pub top: i32,
+    pub advance: usize,
+    pub bitmap: Vec<u8>,
+}
+
+impl Glyph {
+    pub fn coverage(&self, x: usize, y: usize) -> u8 {
+        self.bitmap[y * self.width + x]
+    }
+
+    fn from_letter(letter: &Letter) -> Self {
+        Self {
+            width: LETTER_WIDTH,
+            height: LETTER_HEIGHT,
+            left: 0,
+            top: 0,
+            advance: LETTER_WIDTH + 1,
+            bitmap: letter.iter().map(|b| b * 0xFF).collect(),
+        }
+    }
+
+    /// A solid block, used for characters that a font has no glyph for.
+    pub(super) fn block(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            left: 0,
+            top: 0,
+            advance: width + 1,
+            bitmap: vec![0xFF; width * height],
+        }
+    }
+}
+
+pub struct BitmapFont {
+    glyphs: HashMap<char, Glyph>,
+    unknown: Glyph,
+    height: usize,
+}
+
+impl BitmapFont {
+    pub(super) fn new(glyphs: HashMap<char, Glyph>, unknown: Glyph, height: usize) -> Self {
+        Self {
+            glyphs,
+            unknown,
+            height,
+        }
+    }
+
+    /// The built-in 5x7 font
+    fn builtin() -> Self {
+        let glyphs = (' '..='~')
+            .map(|c| (c, Glyph::from_letter(super::letter(c))))
+            .collect();
+
+        Self::new(glyphs, Glyph::from_letter(&UNKNOWN), LETTER_HEIGHT)
+    }
+}
+
+pub enum Font {
+    Bitmap(BitmapFont),
+    Scalable(truetype::ScalableFont),
+}
+
+impl Font {
+    /// The built-in 5x7 font
+    pub fn builtin() -> Self {
+        Self::Bitmap(BitmapFont::builtin())
+    }
+
+    /// Load a BDF, PSF (version 1 or 2), TrueType or OpenType font. The
+    /// format is detected from the contents of the file.
+    ///
+    /// `size` is the height in pixels that scalable fonts are rasterized at.
+    pub fn load(path: &Path, size: f32) -> Result<Self, FontError> {
+        let data = std::fs::read(path)?;
+
+        if data.starts_with(&psf::PSF1_MAGIC) || data.starts_with(&psf::PSF2_MAGIC) {
+            psf::parse(&data).map(Self::Bitmap)
+        } else if data.starts_with(b"STARTFONT") {
+            bdf::parse(&data).map(Self::Bitmap)
+        } else if truetype::MAGICS.iter().any(|m| data.starts_with(m)) {
+            truetype::ScalableFont::new(data, size).map(Self::Scalable)
+        } else {
+            Err(FontError::UnknownFormat)
+        }
+    }
+
+    pub fn glyph(&self, c: char) -> Cow<'_, Glyph> {
+        match self {
+            Font::Bitmap(font) => Cow::Borrowed(font.glyphs.get(&c).unwrap_or(&font.unknown)),
+            Font::Scalable(font) => Cow::Owned(font.glyph(c)),
+        }
+    }
+
+    /// The adjustment to the advance between two characters, in pixels
+    pub fn kern(&self, first: char, second: char) -> i32 {
+        match self {
+            Font::Bitmap(_) => 0,
+            Font::Scalable(font) => font.kern(first, second),
+        }
+    }
+
+    /// The height of a line of text, in pixels
+    pub fn height(&self) -> usize {
+        match self {
+            Font::Bitmap(font) => font.height,
+            Font::Scalable(font) => font.height(),
+        }
+    }
+
+    pub fn is_scalable(&self) -> bool {
+        matches!(self, Font::Scalable(_))
+    }
+}
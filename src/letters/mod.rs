@@ -1,38 +1,132 @@
+mod bdf;
 mod consts;
-use std::slice::Iter;
+mod font;
+mod psf;
+mod truetype;
+use std::{borrow::Cow, slice::Iter};
 
 pub use consts::*;
+pub use font::{Font, Glyph};
+
+/// Look up the built-in 5x7 letter for a character
+fn letter(character: char) -> &'static Letter {
+    match character {
+        'a' | 'A' => &A,
+        'b' | 'B' => &B,
+        'c' | 'C' => &C,
+        'd' | 'D' => &D,
+        'e' | 'E' => &E,
+        'f' | 'F' => &F,
+        'g' | 'G' => &G,
+        'h' | 'H' => &H,
+        'i' | 'I' => &I,
+        'j' | 'J' => &J,
+        'k' | 'K' => &K,
+        'l' | 'L' => &L,
+        'm' | 'M' => &M,
+        'n' | 'N' => &N,
+        'o' | 'O' => &O,
+        'p' | 'P' => &P,
+        'q' | 'Q' => &Q,
+        'r' | 'R' => &R,
+        's' | 'S' => &S,
+        't' | 'T' => &T,
+        'u' | 'U' => &U,
+        'v' | 'V' => &V,
+        'w' | 'W' => &W,
+        'x' | 'X' => &X,
+        'y' | 'Y' => &Y,
+        'z' | 'Z' => &Z,
+        '0' => &ZERO,
+        '1' => &ONE,
+        '2' => &TWO,
+        '3' => &THREE,
+        '4' => &FOUR,
+        '5' => &FIVE,
+        '6' => &SIX,
+        '7' => &SEVEN,
+        '8' => &EIGHT,
+        '9' => &NINE,
+        '!' => &EXCLAMATION,
+        '"' => &QUOTE,
+        '#' => &HASH,
+        '$' => &DOLLAR,
+        '%' => &PERCENT,
+        '&' => &AMPERSAND,
+        '\'' => &APOSTROPHE,
+        '(' => &LEFT_PAREN,
+        ')' => &RIGHT_PAREN,
+        '*' => &ASTERISK,
+        '+' => &PLUS,
+        ',' => &COMMA,
+        '-' => &MINUS,
+        '.' => &PERIOD,
+        '/' => &SLASH,
+        ':' => &COLON,
+        ';' => &SEMICOLON,
+        '<' => &LESS_THAN,
+        '=' => &EQUALS,
+        '>' => &GREATER_THAN,
+        '?' => &QUESTION,
+        '@' => &AT,
+        '[' => &LEFT_BRACKET,
+        '\\' => &BACKSLASH,
+        ']' => &RIGHT_BRACKET,
+        '^' => &CARET,
+        '_' => &UNDERSCORE,
+        '`' => &BACKTICK,
+        '{' => &LEFT_BRACE,
+        '|' => &PIPE,
+        '}' => &RIGHT_BRACE,
+        '~' => &TILDE,
+        ' ' => &SPACE,
+        _ => &UNKNOWN,
+    }
+}
 
 pub struct LetterString<'a> {
-    pub letters: Vec<&'a Letter>,
+    pub letters: Vec<Cow<'a, Glyph>>,
 }
 
-impl<'a> From<&str> for LetterString<'a> {
-    fn from(string: &str) -> Self {
-        let mut letters = Vec::new();
-        for character in string.chars() {
-            match character {
-                'a' | 'A' => letters.push(&A),
-                'b' | 'B' => letters.push(&B),
-                'c' | 'C' => letters.push(&C),
-                'd' | 'D' => letters.push(&D),
-                'e' | 'E' => letters.push(&E),
-                'g' | 'G' => letters.push(&G),
-                'h' | 'H' => letters.push(&H),
-                'l' | 'L' => letters.push(&L),
-                'm' | 'M' => letters.push(&M),
-                'n' | 'N' => letters.push(&N),
-                'u' | 'U' => letters.push(&U),
-                ' ' => letters.push(&SPACE),
-                _ => letters.push(&UNKNOWN),
+impl<'a> LetterString<'a> {
+    pub fn new(font: &'a Font, string: &str) -> Self {
+        let mut letters: Vec<Cow<'a, Glyph>> = Vec::new();
+        let mut previous = None;
+
+        for c in string.chars() {
+            if let (Some(previous), Some(last)) = (previous, letters.last_mut()) {
+                let kern = font.kern(previous, c);
+                if kern != 0 {
+                    let last = last.to_mut();
+                    last.advance = (last.advance as i32 + kern).max(0) as usize;
+                }
             }
+
+            letters.push(font.glyph(c));
+            previous = Some(c);
         }
+
         Self { letters }
     }
-}
 
-impl<'a> LetterString<'a> {
-    pub fn iter(&self) -> Iter<&Letter> {
+    pub fn iter(&self) -> Iter<'_, Cow<'a, Glyph>> {
         self.letters.iter()
     }
+
+    /// The width of the string, in pixels
+    pub fn width(&self) -> usize {
+        self.letters.iter().map(|l| l.advance).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_printable_character_has_a_glyph() {
+        for c in ' '..='~' {
+            assert_ne!(letter(c), &UNKNOWN, "{:?} has no glyph", c);
+        }
+    }
 }
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+
+use super::font::{BitmapFont, FontError, Glyph};
+
+pub const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+pub const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HAS_TAB: u8 = 0x02;
+const PSF1_MODE_HAS_SEQ: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_START_SEQ: u16 = 0xFFFE;
+
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_START_SEQ: u8 = 0xFE;
+
+struct Header {
+    glyph_count: usize,
+    glyph_size: usize,
+    width: usize,
+    height: usize,
+    glyphs_start: usize,
+}
+
+/// Parse a PC Screen Font, either version 1 or 2.
+pub fn parse(data: &[u8]) -> Result<BitmapFont, FontError> {
+    if data.starts_with(&PSF1_MAGIC) {
+        parse_psf1(data)
+    } else if data.starts_with(&PSF2_MAGIC) {
+        parse_psf2(data)
+    } else {
+        Err(FontError::UnknownFormat)
+    }
+}
+
+fn too_short() -> FontError {
+    FontError::Invalid(String::from("File is too short"))
+}
+
+impl Header {
+    /// Check that the glyph dimensions make sense, and that all glyphs fit
+    /// in a file of `len` bytes
+    fn validate(&self, len: usize) -> Result<(), FontError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(FontError::Invalid(String::from("Glyphs have no size")));
+        }
+
+        if self.glyph_count == 0 {
+            return Err(FontError::Invalid(String::from("Font has no glyphs")));
+        }
+
+        match self.width.div_ceil(8).checked_mul(self.height) {
+            Some(bitmap_size) if bitmap_size <= self.glyph_size => {}
+            _ => {
+                return Err(FontError::Invalid(String::from(
+                    "Glyph size is too small for the glyph dimensions",
+                )))
+            }
+        }
+
+        let glyphs_end = self
+            .glyph_count
+            .checked_mul(self.glyph_size)
+            .and_then(|size| size.checked_add(self.glyphs_start));
+        match glyphs_end {
+            Some(end) if end <= len => Ok(()),
+            _ => Err(too_short()),
+        }
+    }
+}
+
+fn parse_psf1(data: &[u8]) -> Result<BitmapFont, FontError> {
+    if data.len() < 4 {
+        return Err(too_short());
+    }
+
+    let mode = data[2];
+    let header = Header {
+        glyph_count: if mode & PSF1_MODE_512 != 0 { 512 } else { 256 },
+        glyph_size: data[3] as usize,
+        width: 8,
+        height: data[3] as usize,
+        glyphs_start: 4,
+    };
+    header.validate(data.len())?;
+
+    let table = if mode & (PSF1_MODE_HAS_TAB | PSF1_MODE_HAS_SEQ) != 0 {
+        let start = header.glyphs_start + header.glyph_count * header.glyph_size;
+        let table = data.get(start..).ok_or_else(too_short)?;
+        Some(psf1_unicode_table(table, header.glyph_count))
+    } else {
+        None
+    };
+
+    build_font(data, &header, table)
+}
+
+fn parse_psf2(data: &[u8]) -> Result<BitmapFont, FontError> {
+    if data.len() < 32 {
+        return Err(too_short());
+    }
+
+    let field = |idx: usize| {
+        let start = 4 + idx * 4;
+        u32::from_le_bytes([
+            data[start],
+            data[start + 1],
+            data[start + 2],
+            data[start + 3],
+        ])
+    };
+
+    let flags = field(2);
+    let header = Header {
+        glyph_count: field(3) as usize,
+        glyph_size: field(4) as usize,
+        height: field(5) as usize,
+        width: field(6) as usize,
+        glyphs_start: field(1) as usize,
+    };
+    header.validate(data.len())?;
+
+    let table = if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+        let start = header.glyphs_start + header.glyph_count * header.glyph_size;
+        let table = data.get(start..).ok_or_else(too_short)?;
+        Some(psf2_unicode_table(table, header.glyph_count))
+    } else {
+        None
+    };
+
+    build_font(data, &header, table)
+}
+
+/// Map every glyph to the characters it represents. Multi-character
+/// sequences are skipped, as we only ever look up single characters.
+fn psf1_unicode_table(table: &[u8], glyph_count: usize) -> Vec<Vec<char>> {
+    let mut mapping = vec![Vec::new(); glyph_count];
+    let mut values = table
+        .chunks_exact(2)
+        .map(|value| u16::from_le_bytes([value[0], value[1]]));
+
+    for chars in mapping.iter_mut() {
+        let mut in_sequence = false;
+        for value in values.by_ref() {
+            match value {
+                PSF1_SEPARATOR => break,
+                PSF1_START_SEQ => in_sequence = true,
+                value if !in_sequence => chars.extend(char::from_u32(value as u32)),
+                _ => {}
+            }
+        }
+    }
+
+    mapping
+}
+
+fn psf2_unicode_table(table: &[u8], glyph_count: usize) -> Vec<Vec<char>> {
+    let mut mapping = vec![Vec::new(); glyph_count];
+    let mut entries = table.split(|b| *b == PSF2_SEPARATOR);
+
+    for (chars, entry) in mapping.iter_mut().zip(entries.by_ref()) {
+        let singles = entry.split(|b| *b == PSF2_START_SEQ).next().unwrap_or(&[]);
+        chars.extend(String::from_utf8_lossy(singles).chars());
+    }
+
+    mapping
+}
+
+fn build_font(
+    data: &[u8],
+    header: &Header,
+    table: Option<Vec<Vec<char>>>,
+) -> Result<BitmapFont, FontError> {
+    let row_size = header.width.div_ceil(8);
+
+    let mut glyphs = HashMap::new();
+    for idx in 0..header.glyph_count {
+        let start = header.glyphs_start + idx * header.glyph_size;
+        let raw = data
+            .get(start..start + header.glyph_size)
+            .ok_or_else(too_short)?;
+
+        let mut bitmap = Vec::with_capacity(header.width * header.height);
+        for row in raw.chunks(row_size).take(header.height) {
+            for x in 0..header.width {
+                bitmap.push(((row[x / 8] >> (7 - (x % 8))) & 1) * 0xFF);
+            }
+        }
+
+        let glyph = Glyph {
+            width: header.width,
+            height: header.height,
+            left: 0,
+            top: 0,
+            advance: header.width,
+            bitmap,
+        };
+
+        match &table {
+            Some(table) => {
+                for c in &table[idx] {
+                    glyphs.insert(*c, glyph.clone());
+                }
+            }
+            None => {
+                if let Some(c) = char::from_u32(idx as u32) {
+                    glyphs.insert(c, glyph);
+                }
+            }
+        }
+    }
+
+    let mut unknown = Glyph::block(header.width, header.height);
+    unknown.advance = header.width;
+
+    Ok(BitmapFont::new(glyphs, unknown, header.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::letters::Font;
+
+    fn psf1(mode: u8, height: u8) -> Vec<u8> {
+        let glyph_count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+        let mut data = vec![PSF1_MAGIC[0], PSF1_MAGIC[1], mode, height];
+        data.resize(4 + glyph_count * height as usize, 0);
+        data
+    }
+
+    fn psf2(width: u32, height: u32, glyph_size: u32, glyph_count: u32, flags: u32) -> Vec<u8> {
+        let mut data = PSF2_MAGIC.to_vec();
+        for field in [0, 32, flags, glyph_count, glyph_size, height, width].iter() {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.resize(32 + (glyph_count * glyph_size) as usize, 0);
+        data
+    }
+
+    fn is_invalid(result: Result<BitmapFont, FontError>) -> bool {
+        matches!(result, Err(FontError::Invalid(_)))
+    }
+
+    #[test]
+    fn psf1_glyphs() {
+        let mut data = psf1(0, 2);
+        data[4 + 'A' as usize * 2] = 0b1000_0000;
+        data[4 + 'A' as usize * 2 + 1] = 0b0000_0001;
+
+        let font = Font::Bitmap(parse(&data).unwrap());
+        assert_eq!(font.height(), 2);
+
+        let glyph = font.glyph('A');
+        assert_eq!((glyph.width, glyph.height, glyph.advance), (8, 2, 8));
+        let mut expected = vec![0; 16];
+        expected[0] = 0xFF;
+        expected[15] = 0xFF;
+        assert_eq!(glyph.bitmap, expected);
+    }
+
+    #[test]
+    fn psf1_unicode_table() {
+        let mut data = psf1(PSF1_MODE_HAS_TAB, 1);
+        data[4] = 0b1010_1010;
+        // Glyph 0 is 'é' and the sequence 'e' + combining acute accent,
+        // glyph 1 is 'x', and every other glyph has no characters
+        for value in [
+            0xE9,
+            PSF1_START_SEQ,
+            'e' as u16,
+            0x301,
+            PSF1_SEPARATOR,
+            'x' as u16,
+            PSF1_SEPARATOR,
+        ]
+        .iter()
+        {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for _ in 2..256 {
+            data.extend_from_slice(&PSF1_SEPARATOR.to_le_bytes());
+        }
+
+        let font = Font::Bitmap(parse(&data).unwrap());
+        assert_eq!(font.glyph('é').bitmap, [0xFF, 0].repeat(4));
+        assert_eq!(font.glyph('x').bitmap, vec![0; 8]);
+        // Characters that are only part of a sequence don't get a glyph, so
+        // they are drawn as a solid block
+        assert_eq!(font.glyph('e').bitmap, vec![0xFF; 8]);
+    }
+
+    #[test]
+    fn psf2_glyphs() {
+        let mut data = psf2(10, 2, 4, 2, PSF2_HAS_UNICODE_TABLE);
+        // Glyph 1 has rows of two bytes, as it is more than 8 pixels wide
+        data[32 + 4..32 + 8].copy_from_slice(&[0b1000_0000, 0b0100_0000, 0, 0]);
+        data.extend_from_slice(b"a\xFFb\xFEbc\xFF");
+
+        let font = Font::Bitmap(parse(&data).unwrap());
+        let glyph = font.glyph('b');
+        assert_eq!((glyph.width, glyph.height, glyph.advance), (10, 2, 10));
+        let mut expected = vec![0; 20];
+        expected[0] = 0xFF;
+        expected[9] = 0xFF;
+        assert_eq!(glyph.bitmap, expected);
+        assert_eq!(font.glyph('a').bitmap, vec![0; 20]);
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(is_invalid(parse(&PSF1_MAGIC)));
+        assert!(is_invalid(parse(&psf1(0, 8)[..100])));
+        assert!(is_invalid(parse(&psf2(8, 8, 8, 4, 0)[..20])));
+        assert!(is_invalid(parse(&psf2(8, 8, 8, 4, 0)[..60])));
+    }
+
+    #[test]
+    fn malformed_headers() {
+        assert!(matches!(parse(b"PSF"), Err(FontError::UnknownFormat)));
+        assert!(is_invalid(parse(&psf1(0, 0))));
+
+        let empty = |width, height, glyph_size, glyph_count| {
+            let mut data = psf2(0, 0, 0, 0, 0);
+            data[16..20].copy_from_slice(&u32::to_le_bytes(glyph_count));
+            data[20..24].copy_from_slice(&u32::to_le_bytes(glyph_size));
+            data[24..28].copy_from_slice(&u32::to_le_bytes(height));
+            data[28..32].copy_from_slice(&u32::to_le_bytes(width));
+            data.extend_from_slice(&[0; 64]);
+            data
+        };
+        assert!(is_invalid(parse(&empty(0, 8, 8, 1))));
+        assert!(is_invalid(parse(&empty(8, 0, 8, 1))));
+        assert!(is_invalid(parse(&empty(8, 8, 8, 0))));
+        assert!(is_invalid(parse(&empty(0, 0, 0, u32::MAX))));
+        assert!(is_invalid(parse(&empty(16, 8, 8, 1))));
+        assert!(is_invalid(parse(&empty(8, 8, u32::MAX, u32::MAX))));
+    }
+}
@@ -0,0 +1,78 @@
+use ab_glyph::{point, Font as _, FontVec, PxScale, ScaleFont as _};
+
+use super::font::{FontError, Glyph};
+
+/// The magic numbers of TrueType, OpenType and TrueType collection files
+pub const MAGICS: [&[u8]; 4] = [&[0x00, 0x01, 0x00, 0x00], b"OTTO", b"true", b"ttcf"];
+
+/// A TrueType or OpenType font, rasterized at a fixed pixel size
+pub struct ScalableFont {
+    font: FontVec,
+    scale: PxScale,
+}
+
+impl ScalableFont {
+    pub fn new(data: Vec<u8>, size: f32) -> Result<Self, FontError> {
+        let font = FontVec::try_from_vec(data).map_err(|e| FontError::Invalid(e.to_string()))?;
+
+        Ok(Self {
+            font,
+            scale: PxScale::from(size),
+        })
+    }
+
+    pub fn glyph(&self, c: char) -> Glyph {
+        let font = self.font.as_scaled(self.scale);
+
+        let mut glyph = font.scaled_glyph(c);
+        glyph.position = point(0.0, font.ascent());
+        let advance = font.h_advance(glyph.id).round().max(0.0) as usize;
+
+        let outline = match font.outline_glyph(glyph) {
+            Some(outline) => outline,
+            // Glyphs without an outline, such as spaces, only advance the pen
+            None => {
+                return Glyph {
+                    width: 0,
+                    height: 0,
+                    left: 0,
+                    top: 0,
+                    advance,
+                    bitmap: Vec::new(),
+                }
+            }
+        };
+
+        let bounds = outline.px_bounds();
+        let width = bounds.width() as usize;
+        let height = bounds.height() as usize;
+
+        let mut bitmap = vec![0; width * height];
+        outline.draw(|x, y, coverage| {
+            let (x, y) = (x as usize, y as usize);
+            if x < width && y < height {
+                bitmap[y * width + x] = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        });
+
+        Glyph {
+            width,
+            height,
+            left: bounds.min.x as i32,
+            top: bounds.min.y as i32,
+            advance,
+            bitmap,
+        }
+    }
+
+    pub fn kern(&self, first: char, second: char) -> i32 {
+        let font = self.font.as_scaled(self.scale);
+        font.kern(font.glyph_id(first), font.glyph_id(second))
+            .round() as i32
+    }
+
+    pub fn height(&self) -> usize {
+        let font = self.font.as_scaled(self.scale);
+        (font.ascent() - font.descent()).ceil() as usize
+    }
+}
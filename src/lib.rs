@@ -0,0 +1,27 @@
+pub mod canvas;
+pub mod capabilities;
+pub mod codec;
+pub mod color;
+pub mod compression;
+pub mod connection;
+pub mod decoder;
+pub mod defender;
+pub mod dialect;
+pub mod dictionary;
+pub mod dryrun;
+pub mod fill;
+pub mod framequeue;
+pub mod gif;
+pub mod image;
+pub mod letters;
+pub mod mockserver;
+pub mod pixelcollector;
+pub mod ratelimit;
+pub mod recording;
+pub mod remote;
+pub mod replay;
+pub mod screenshot;
+pub mod snake;
+pub mod stats;
+pub mod text;
+pub mod window;
@@ -1,29 +1,48 @@
 use clap::Parser;
-use codec::{Codec, CodecData, DataProducer, RunError, SetupError};
-use fill::Fill;
-use pixelcollector::CompressionKind;
-use snake::Snake;
-use std::{net::TcpStream, path::PathBuf, time::Duration};
-
-mod codec;
-mod color;
-mod fill;
-mod gif;
-mod image;
-mod letters;
-mod pixelcollector;
-mod snake;
-mod window;
-
-use crate::image::Image;
-use crate::{codec::CodecOptions, gif::Gif};
-use color::Color;
-
-#[derive(Debug)]
+use pixelflut_filler::{
+    canvas::{Canvas, Region},
+    codec::{
+        Codec, CodecData, CodecOptions, DataProducer, Frame, ReconnectOptions, RunError, SetupError,
+    },
+    color::Color,
+    compression::CompressionKind,
+    connection::Connector,
+    defender::Defender,
+    dialect::Dialect,
+    dictionary::{self, Dictionary},
+    dryrun::{self, DryRun},
+    fill::Fill,
+    framequeue::QueuePolicy,
+    gif::Gif,
+    image::Image,
+    recording::RecordingReader,
+    remote::Remote,
+    replay::Replay,
+    screenshot::Screenshot,
+    snake::Snake,
+    text::Text,
+};
+use std::{num::NonZeroU64, path::PathBuf, time::Duration};
+
 enum Error {
     Io(std::io::Error),
     SetupError(SetupError),
     RunError(RunError),
+    Dictionary(String),
+    DryRun(String),
+}
+
+/// `main` prints the error it returns with `Debug`, so print the error
+/// itself rather than which variant it is wrapped in
+impl std::fmt::Debug for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::SetupError(e) => write!(f, "{:?}", e),
+            Error::RunError(e) => write!(f, "{:?}", e),
+            Error::Dictionary(message) | Error::DryRun(message) => f.write_str(message),
+        }
+    }
 }
 
 impl From<SetupError> for Error {
@@ -50,18 +69,115 @@ impl From<std::io::Error> for Error {
     about = "Fill a pixelflut instance's window"
 )]
 struct Opt {
-    /// The remote to connect to
+    /// The remote to connect to. Either `host`, `host:port`,
+    /// `[ipv6]:port` or `unix:/path/to/socket`
     #[clap(global = true, short, long, default_value = "127.0.0.1")]
-    remote: String,
+    remote: Remote,
 
     /// Use the binary protocol
     #[clap(global = true, short = 'b', long)]
     use_binary_protocol: bool,
 
-    /// What type of compression to use
+    /// The variant of the protocol that the server speaks: generic,
+    /// breakwater, shoreline or pixelnuke
+    #[clap(global = true, long, default_value_t = Dialect::GENERIC)]
+    dialect: Dialect,
+
+    /// Write colors in lowercase hex instead of uppercase
+    #[clap(global = true, long)]
+    lowercase_hex: bool,
+
+    /// Send an `OFFSET` before the pixels of every frame, so that the
+    /// coordinates of text `PX` commands are shorter
+    #[clap(global = true, long)]
+    use_offset: bool,
+
+    /// What type of compression to use: zstd, deflate or gzip.
+    /// Optionally followed by a level, e.g. `zstd:3`
     #[clap(global = true, short, long)]
     compression: Option<CompressionKind>,
 
+    /// The amount of connections to send pixel data over in parallel
+    #[clap(global = true, long, default_value = "1")]
+    connections: usize,
+
+    /// How long to wait for the server to reply, in milliseconds. Applies to
+    /// the handshake and to reading pixels back
+    #[clap(global = true, long, default_value = "5000")]
+    read_timeout: u64,
+
+    /// Reconnect when the connection to the server is lost
+    #[clap(global = true, long)]
+    reconnect: bool,
+
+    /// The longest time to wait between reconnection attempts, in milliseconds
+    #[clap(global = true, long, default_value = "30000")]
+    max_backoff: u64,
+
+    /// The most bytes to send per second, across all connections
+    #[clap(global = true, long)]
+    max_bandwidth: Option<NonZeroU64>,
+
+    /// The most pixels to send per second, across all connections
+    #[clap(global = true, long)]
+    max_pixels_per_second: Option<NonZeroU64>,
+
+    /// How often to log throughput statistics, in seconds
+    #[clap(global = true, long, default_value = "10")]
+    stats_interval: u64,
+
+    /// Write throughput statistics to this file in the Prometheus text
+    /// format, every time they are logged
+    #[clap(global = true, long)]
+    prometheus_file: Option<PathBuf>,
+
+    /// Don't ask the server which commands it supports with `HELP`. By
+    /// default, options that the server does not support are turned off
+    #[clap(global = true, long)]
+    skip_help: bool,
+
+    /// A zstd dictionary, made with `train-dict`, to compress with.
+    /// Implies zstd compression
+    #[clap(global = true, long)]
+    dictionary: Option<PathBuf>,
+
+    /// Keep the output of the command on the canvas: read it back at this
+    /// interval, in milliseconds, and repaint pixels that were overwritten
+    #[clap(global = true, long)]
+    defend: Option<u64>,
+
+    /// Record every frame that is sent, with the time it was sent at, to
+    /// this file. Recordings can be played back with `replay`
+    #[clap(global = true, long)]
+    record: Option<PathBuf>,
+
+    /// Stop after sending this many frames. Dry runs of commands that never
+    /// finish, like `snake`, stop after 100 frames by default
+    #[clap(global = true, long)]
+    max_frames: Option<u64>,
+
+    /// The most frames to make ahead of sending them, on a separate thread.
+    /// With 0, frames are made and sent on the same thread
+    #[clap(global = true, long, default_value = "0")]
+    queue_depth: usize,
+
+    /// What to do when the queue of frames is full: block, to wait until a
+    /// frame was sent, or drop-oldest, to always send the most recent frames.
+    /// `snake` and `replay` only send changes, so their frames can't be dropped
+    #[clap(global = true, long, default_value_t = QueuePolicy::Block)]
+    queue_policy: QueuePolicy,
+
+    /// Don't connect to a server, but draw on an in-memory canvas of this
+    /// size, as `WIDTHxHEIGHT`, and save it to `--dry-run-output`.
+    /// Compression is not used in dry runs
+    #[clap(global = true, long, parse(try_from_str = parse_canvas_size))]
+    dry_run: Option<Region>,
+
+    /// Where to save the canvas of a dry run. The last frame is saved as an
+    /// image, or every frame as an animation if this ends with `.gif`
+    #[clap(global = true, long, default_value = "dry-run.png")]
+    dry_run_output: PathBuf,
+
     /// The command to execute
     #[clap(subcommand)]
     command: Command,
@@ -83,6 +199,13 @@ enum Command {
     Write(WriteCommand),
     /// Create a snake that wiggles along the screen
     Snake,
+    /// Read the canvas back from the server, and save it as a PNG
+    Screenshot(ScreenshotCommand),
+    /// Train a zstd dictionary for `--dictionary` on files of encoded frames
+    TrainDict(TrainDictCommand),
+    /// Send the frames of a recording made with `--record`. The recording
+    /// decides the protocol options and the amount of connections
+    Replay(ReplayCommand),
 }
 
 #[derive(Parser)]
@@ -101,11 +224,19 @@ struct WriteCommand {
     y: Option<usize>,
     /// The color to use when drawing. Defaults
     /// to a random color per letter
-    #[clap(short, default_value = "Color::random")]
-    color: Color,
+    #[clap(long)]
+    color: Option<Color>,
     /// The text to write
     #[clap(default_value = "change me")]
     text: String,
+    /// A BDF, PSF, TrueType or OpenType font to draw
+    /// the text with. Defaults to the built-in 5x7 font
+    #[clap(long)]
+    font: Option<PathBuf>,
+    /// The height in pixels to draw TrueType and OpenType
+    /// fonts at. Defaults to 7 times the scale
+    #[clap(long)]
+    font_size: Option<f32>,
     /// Fill the screen with the specified
     /// color before writing the text. Use 'r'
     /// to use a random color.
@@ -146,11 +277,53 @@ struct ImageCommand {
     frame_interval: Option<u64>,
 }
 
+#[derive(Parser)]
+struct ScreenshotCommand {
+    /// The file to save the screenshot to
+    #[clap(default_value = "screenshot.png")]
+    file_name: PathBuf,
+    /// The part of the canvas to save, as `WIDTHxHEIGHT+X+Y`.
+    /// Defaults to the whole canvas
+    #[clap(long)]
+    region: Option<Region>,
+}
+
+#[derive(Parser)]
+struct TrainDictCommand {
+    /// The file to write the dictionary to
+    #[clap(short, long)]
+    output: PathBuf,
+    /// The maximum size of the dictionary, in bytes
+    #[clap(long, default_value_t = dictionary::DEFAULT_MAX_SIZE)]
+    max_size: usize,
+    /// The size of the samples that the files are split into, in bytes
+    #[clap(long, default_value_t = dictionary::DEFAULT_SAMPLE_SIZE)]
+    sample_size: usize,
+    /// Files with encoded frames (text, or binary in the layout of --dialect)
+    /// to train on
+    #[clap(required = true)]
+    samples: Vec<PathBuf>,
+}
+
+#[derive(Parser)]
+struct ReplayCommand {
+    /// The recording to send
+    file_name: PathBuf,
+    /// How much faster than recorded to send the frames, e.g. `0.5` for
+    /// half the speed
+    #[clap(long, default_value = "1")]
+    speed: f64,
+}
+
 enum DataProducers {
     Gif(Gif),
     Fill(Fill),
     Image(Image),
     Snake(Snake),
+    Text(Text),
+    Screenshot(Screenshot),
+    Replay(Replay),
+    Defend(Box<Defender<DataProducers>>),
 }
 
 impl DataProducer for DataProducers {
@@ -160,15 +333,54 @@ impl DataProducer for DataProducers {
             DataProducers::Fill(fill) => fill.do_setup(data),
             DataProducers::Snake(snake) => snake.do_setup(data),
             DataProducers::Image(image) => image.do_setup(data),
+            DataProducers::Text(text) => text.do_setup(data),
+            DataProducers::Screenshot(screenshot) => screenshot.do_setup(data),
+            DataProducers::Replay(replay) => replay.do_setup(data),
+            DataProducers::Defend(defender) => defender.do_setup(data),
         }
     }
 
-    fn get_next_data(&mut self) -> Result<(Vec<u8>, Option<Duration>), RunError> {
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
         match self {
             DataProducers::Gif(gif) => gif.get_next_data(),
             DataProducers::Fill(fill) => fill.get_next_data(),
             DataProducers::Snake(snake) => snake.get_next_data(),
             DataProducers::Image(image) => image.get_next_data(),
+            DataProducers::Text(text) => text.get_next_data(),
+            DataProducers::Screenshot(screenshot) => screenshot.get_next_data(),
+            DataProducers::Replay(replay) => replay.get_next_data(),
+            DataProducers::Defend(defender) => defender.get_next_data(),
+        }
+    }
+
+    fn readback_region(&self) -> Option<Region> {
+        match self {
+            DataProducers::Screenshot(screenshot) => screenshot.readback_region(),
+            DataProducers::Defend(defender) => defender.readback_region(),
+            _ => None,
+        }
+    }
+
+    fn readback_pixels(&self) -> Option<&[(usize, usize)]> {
+        match self {
+            DataProducers::Defend(defender) => defender.readback_pixels(),
+            _ => None,
+        }
+    }
+
+    fn incremental(&self) -> bool {
+        match self {
+            DataProducers::Snake(snake) => snake.incremental(),
+            DataProducers::Replay(replay) => replay.incremental(),
+            _ => false,
+        }
+    }
+
+    fn update_canvas(&mut self, canvas: &Canvas) {
+        match self {
+            DataProducers::Screenshot(screenshot) => screenshot.update_canvas(canvas),
+            DataProducers::Defend(defender) => defender.update_canvas(canvas),
+            _ => {}
         }
     }
 }
@@ -178,9 +390,64 @@ fn main() -> Result<(), Error> {
 
     let opt = Opt::from_args();
 
+    let (compression_kind, dictionary) = match opt.dictionary {
+        Some(path) => {
+            let dictionary = Dictionary::load(path).map_err(Error::Dictionary)?;
+            match opt.compression {
+                None => (Some(CompressionKind::Zstd(1)), Some(dictionary)),
+                Some(kind @ CompressionKind::Zstd(_)) => (Some(kind), Some(dictionary)),
+                Some(kind) => {
+                    return Err(Error::Dictionary(format!(
+                        "Dictionaries can only be used with zstd, not {}",
+                        kind
+                    )))
+                }
+            }
+        }
+        None => (opt.compression, None),
+    };
+
     let remote = opt.remote;
 
-    let stream = TcpStream::connect(format!("{}:1337", remote))?;
+    let mut options = CodecOptions {
+        compression_kind,
+        dictionary,
+        binary_px: opt.use_binary_protocol,
+        dialect: opt.dialect,
+        lowercase_hex: opt.lowercase_hex,
+        offset: opt.use_offset,
+        read_timeout: Duration::from_millis(opt.read_timeout),
+        connections: opt.connections.max(1),
+        max_bandwidth: opt.max_bandwidth,
+        max_pixels_per_second: opt.max_pixels_per_second,
+        stats_interval: Duration::from_secs(opt.stats_interval),
+        prometheus_file: opt.prometheus_file,
+        record: opt.record,
+        max_frames: opt.max_frames,
+        queue_depth: opt.queue_depth,
+        queue_policy: opt.queue_policy,
+        probe_capabilities: !opt.skip_help,
+        reconnect: if opt.reconnect {
+            Some(ReconnectOptions {
+                initial_backoff: Duration::from_millis(250),
+                max_backoff: Duration::from_millis(opt.max_backoff),
+            })
+        } else {
+            None
+        },
+    };
+
+    // Data producers that keep sending frames until they are stopped
+    let endless = opt.defend.is_some()
+        || matches!(
+            opt.command,
+            Command::Gif(_)
+                | Command::Snake
+                | Command::Image(ImageCommand {
+                    frame_interval: Some(_),
+                    ..
+                })
+        );
 
     let data_producer = match opt.command {
         Command::Gif(gif) => DataProducers::Gif(Gif::new(
@@ -195,21 +462,117 @@ fn main() -> Result<(), Error> {
         Command::Snake => DataProducers::Snake(Snake::new()),
         Command::Image(command) => DataProducers::Image(Image::new(
             command.file_name,
-            command.frame_interval.map(|d| Duration::from_millis(d)),
+            command.frame_interval.map(Duration::from_millis),
             command.width_offset,
             command.height_offset,
         )),
-        Command::Write(_) => todo!(),
+        Command::Write(command) => DataProducers::Text(Text::new(
+            command.text,
+            command.font,
+            command.font_size,
+            command.scale,
+            command.x,
+            command.y,
+            command.color,
+            command.count,
+            command.fill_color,
+        )),
+        Command::Screenshot(command) => {
+            DataProducers::Screenshot(Screenshot::new(command.file_name, command.region))
+        }
+        Command::Replay(command) => {
+            let reader = RecordingReader::open(&command.file_name)?;
+            reader.header().apply_to(&mut options);
+            DataProducers::Replay(Replay::new(reader, command.speed))
+        }
+        Command::TrainDict(command) => {
+            let dictionary = dictionary::train(
+                &command.samples,
+                command.sample_size,
+                command.max_size,
+                opt.dialect.binary,
+            )
+            .map_err(Error::Dictionary)?;
+            std::fs::write(&command.output, &dictionary)?;
+            log::info!(
+                "Wrote a {} byte dictionary to {}",
+                dictionary.len(),
+                command.output.display()
+            );
+            return Ok(());
+        }
     };
 
-    let codec = Codec::new(
-        stream,
-        data_producer,
-        CodecOptions {
-            compression_kind: opt.compression,
-            binary_px: opt.use_binary_protocol,
-        },
-    )?;
+    let data_producer = match opt.defend {
+        Some(interval) => DataProducers::Defend(Box::new(Defender::new(
+            data_producer,
+            Duration::from_millis(interval),
+        ))),
+        None => data_producer,
+    };
+
+    match opt.dry_run {
+        Some(size) => {
+            // The in-memory canvas only understands zstd, and compressing
+            // doesn't tell anything about the output
+            if options.compression_kind.take().is_some() {
+                log::info!("Compression is not used in dry runs");
+                options.dictionary = None;
+            }
+
+            if endless && options.max_frames.is_none() {
+                log::info!(
+                    "Stopping after {} frames, use --max-frames to change this",
+                    dryrun::DEFAULT_MAX_FRAMES
+                );
+                options.max_frames = Some(dryrun::DEFAULT_MAX_FRAMES);
+            }
+
+            let dry_run = DryRun::new(size.width, size.height, opt.dry_run_output.clone())
+                .map_err(Error::DryRun)?;
+            run(dry_run.clone(), data_producer, options)?;
+            dry_run.finish().map_err(Error::DryRun)?;
+            log::info!("Saved the canvas to {}", opt.dry_run_output.display());
+        }
+        None => {
+            log::info!("Connecting to {}", remote);
+            let res = run(remote, data_producer, options);
+            std::thread::sleep(Duration::from_secs(1));
+            res?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse the size of the canvas of a dry run, which has no offset. Both
+/// sides have to fit in the `u16` that GIFs and the binary protocol use.
+fn parse_canvas_size(s: &str) -> Result<Region, String> {
+    let region: Region = s.parse()?;
+    if region.x != 0 || region.y != 0 {
+        return Err(format!(
+            "The canvas of a dry run has no offset, expected WIDTHxHEIGHT instead of {}",
+            s
+        ));
+    }
+
+    let max = u16::MAX as usize;
+    if region.width == 0 || region.height == 0 || region.width > max || region.height > max {
+        return Err(format!(
+            "The canvas of a dry run has to be between 1x1 and {}x{} pixels, not {}",
+            max, max, s
+        ));
+    }
+
+    Ok(region)
+}
+
+fn run<C: Connector>(
+    connector: C,
+    data_producer: DataProducers,
+    options: CodecOptions,
+) -> Result<(), Error> {
+    let codec = Codec::new(connector, data_producer, options)?;
 
     log::info!(
         "Detect screen with dimensions x: {}, y: {}",
@@ -217,9 +580,6 @@ fn main() -> Result<(), Error> {
         codec.data().window.get_y()
     );
 
-    let res = codec.run();
-    std::thread::sleep(Duration::from_secs(1));
-
-    res?;
+    codec.run()?;
     Ok(())
 }
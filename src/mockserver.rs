@@ -0,0 +1,304 @@
+//! A small pixelflut server, to test clients against without needing an
+//! external server.
+//!
+//! It supports `HELP`, `SIZE`, `OFFSET`, `COMPRESS ZSTD`, drawing and
+//! reading pixels with text `PX` commands, and drawing pixels with binary
+//! `PB` commands.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    color::Color,
+    decoder::{Command, DecodeError, Decoder},
+    dialect::Dialect,
+};
+
+pub(crate) const HELP: &str = "\
+HELP: Show this help
+SIZE: Get the size of the canvas
+PX x y: Get the color of the pixel at x, y
+PX x y rrggbb(aa): Draw a pixel. Use ww for shades of gray
+PB<x: u16 le><y: u16 le><rgba>: Draw a pixel with the binary protocol
+OFFSET x y: Add x and y to the coordinates of all later PX commands
+COMPRESS ZSTD: Decompress everything after this command with zstd
+";
+
+/// The pixels on the canvas of a [`MockServer`]
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        if x < self.width && y < self.height {
+            Some(self.pixels[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    /// Draw `color` at `x`, `y`, blending it with the current color if it is
+    /// not fully opaque
+    pub fn draw(&mut self, x: usize, y: usize, color: Color) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let pixel = &mut self.pixels[y * self.width + x];
+        let alpha = color.a.unwrap_or(0xFF) as u32;
+        let blend =
+            |new: u8, old: u8| ((new as u32 * alpha + old as u32 * (255 - alpha)) / 255) as u8;
+
+        *pixel = Color::from_rgba(
+            blend(color.r, pixel.r),
+            blend(color.g, pixel.g),
+            blend(color.b, pixel.b),
+            Some(0xFF),
+        );
+    }
+
+    pub fn to_image(&self) -> RgbaImage {
+        RgbaImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let color = self.pixels[y as usize * self.width + x as usize];
+            Rgba([color.r, color.g, color.b, 0xFF])
+        })
+    }
+}
+
+/// The connected clients, by the order in which they connected
+type Clients = Arc<Mutex<HashMap<usize, TcpStream>>>;
+
+/// A pixelflut server that listens in the background
+pub struct MockServer {
+    address: SocketAddr,
+    framebuffer: Arc<Mutex<Framebuffer>>,
+    clients: Clients,
+}
+
+impl MockServer {
+    /// Start listening on `address`, with a canvas of `width` by `height`
+    /// pixels. Use port 0 to listen on any free port.
+    pub fn start<A: ToSocketAddrs>(
+        address: A,
+        width: usize,
+        height: usize,
+    ) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let framebuffer = Arc::new(Mutex::new(Framebuffer::new(width, height)));
+
+        let clients = Clients::default();
+
+        let server_framebuffer = framebuffer.clone();
+        let server_clients = clients.clone();
+        std::thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("Failed to accept a client: {}", e);
+                        continue;
+                    }
+                };
+
+                if let Ok(stream) = stream.try_clone() {
+                    server_clients.lock().unwrap().insert(id, stream);
+                }
+
+                let framebuffer = server_framebuffer.clone();
+                let clients = server_clients.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = Client::new(stream, framebuffer).run() {
+                        log::debug!("Client disconnected: {:?}", e);
+                    }
+                    clients.lock().unwrap().remove(&id);
+                });
+            }
+        });
+
+        Ok(Self {
+            address,
+            framebuffer,
+            clients,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// A copy of the current canvas
+    pub fn framebuffer(&self) -> Framebuffer {
+        self.framebuffer.lock().unwrap().clone()
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        self.framebuffer.lock().unwrap().get(x, y)
+    }
+
+    /// Close the connections of all clients that are connected right now,
+    /// as if the server restarted
+    pub fn disconnect_clients(&self) {
+        for (_, stream) in self.clients.lock().unwrap().drain() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> image::ImageResult<()> {
+        self.framebuffer().to_image().save(path)
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    session: Session,
+}
+
+impl Client {
+    fn new(stream: TcpStream, framebuffer: Arc<Mutex<Framebuffer>>) -> Self {
+        Self {
+            stream,
+            session: Session::new(framebuffer),
+        }
+    }
+
+    fn run(mut self) -> Result<(), DecodeError> {
+        let mut chunk = vec![0u8; 65536];
+        loop {
+            let len = self.stream.read(&mut chunk)?;
+            if len == 0 {
+                return Ok(());
+            }
+
+            let result = self.session.receive(&chunk[..len]);
+            self.stream.write_all(&self.session.take_replies())?;
+            result?;
+        }
+    }
+}
+
+/// What to do after handling a command
+enum Next {
+    Continue,
+    /// Everything after the command is compressed
+    Decompress,
+}
+
+/// The state of a single client: it decodes the commands that the client
+/// sends, draws the pixels on the canvas, and collects the replies
+pub struct Session {
+    decoder: Decoder,
+    replies: Vec<u8>,
+    framebuffer: Arc<Mutex<Framebuffer>>,
+}
+
+impl Session {
+    pub fn new(framebuffer: Arc<Mutex<Framebuffer>>) -> Self {
+        Self {
+            decoder: Decoder::new(Dialect::GENERIC.binary),
+            replies: Vec::new(),
+            framebuffer,
+        }
+    }
+
+    /// Handle `data` that was received from the client. The replies are
+    /// kept until they are taken with [`Session::take_replies`], even if
+    /// this fails.
+    pub fn receive(&mut self, data: &[u8]) -> Result<(), DecodeError> {
+        self.decoder.feed(data)?;
+        self.handle_commands()
+    }
+
+    /// The replies to the commands received so far
+    pub fn take_replies(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.replies)
+    }
+
+    /// Handle all complete commands that were received
+    fn handle_commands(&mut self) -> Result<(), DecodeError> {
+        let framebuffer = self.framebuffer.clone();
+        let mut framebuffer = framebuffer.lock().unwrap();
+
+        while let Some(command) = self.decoder.next_command()? {
+            match self.handle_command(command, &mut framebuffer) {
+                Next::Continue => {}
+                // The rest of the received data is the start of the
+                // compressed stream
+                Next::Decompress => self.decoder.decompress(None)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_command(&mut self, command: Command, framebuffer: &mut Framebuffer) -> Next {
+        match command {
+            Command::Help => self.replies.extend_from_slice(HELP.as_bytes()),
+            Command::Size => self.replies.extend_from_slice(
+                format!("SIZE {} {}\n", framebuffer.width(), framebuffer.height()).as_bytes(),
+            ),
+            Command::Offset { .. } => {}
+            Command::Query { x, y } => {
+                if let Some(color) = framebuffer.get(x, y) {
+                    let (offset_x, offset_y) = self.decoder.offset();
+                    self.replies.extend_from_slice(
+                        format!(
+                            "PX {} {} {:02x}{:02x}{:02x}\n",
+                            x - offset_x,
+                            y - offset_y,
+                            color.r,
+                            color.g,
+                            color.b
+                        )
+                        .as_bytes(),
+                    );
+                }
+            }
+            Command::Pixel { x, y, color } => framebuffer.draw(x, y, color),
+            // Dictionaries are not supported, which is signalled by not echoing
+            // the dictionary ID
+            Command::Compress(arguments)
+                if arguments.len() <= 2
+                    && arguments
+                        .first()
+                        .is_none_or(|kind| kind.eq_ignore_ascii_case("ZSTD")) =>
+            {
+                self.replies.extend_from_slice(b"COMPRESS ZSTD\n");
+                return Next::Decompress;
+            }
+            Command::Compress(_) => self
+                .replies
+                .extend_from_slice(b"ERROR only COMPRESS ZSTD is supported\n"),
+            Command::Unknown(line) => log::debug!("Ignoring unknown command {}", line),
+        }
+
+        Next::Continue
+    }
+}
@@ -1,51 +1,14 @@
-use std::str::FromStr;
-
-use crate::{codec::CodecData, color::Color};
+use crate::{
+    codec::{CodecData, Frame},
+    color::Color,
+    dialect::{BinaryLayout, Dialect},
+};
 
 enum PixelCollectorKind {
-    Binary,
+    Binary(BinaryLayout),
     Text,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum CompressionKind {
-    Zstd,
-}
-
-impl FromStr for CompressionKind {
-    type Err = &'static str;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let kind = match s.to_lowercase().as_str() {
-            "zstd" => Self::Zstd,
-            _ => return Err("unknown compression type"),
-        };
-        Ok(kind)
-    }
-}
-
-impl ToString for CompressionKind {
-    fn to_string(&self) -> String {
-        match self {
-            CompressionKind::Zstd => "zstd".to_string(),
-        }
-    }
-}
-
-impl CompressionKind {
-    pub fn compression_string(&self) -> Vec<u8> {
-        // let val = match self {
-        //     CompressionKind::Zstd => "ZSTD",
-        // };
-
-        format!("COMPRESS\n")
-            .as_bytes()
-            .iter()
-            .map(|u| *u)
-            .collect()
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct PixOffset {
     pub x_max: i32,
@@ -61,13 +24,13 @@ impl PixOffset {
         let x_offset = if self.x_offset >= 0 {
             self.x_offset
         } else {
-            self.x_max as i32 + self.x_offset - self.width as i32
+            self.x_max + self.x_offset - self.width
         };
 
         let y_offset = if self.y_offset >= 0 {
             self.y_offset
         } else {
-            self.y_max as i32 + self.y_offset - self.height as i32
+            self.y_max + self.y_offset - self.height
         };
 
         (x + x_offset, y + y_offset)
@@ -76,24 +39,29 @@ impl PixOffset {
 
 pub struct PixelCollector {
     kind: PixelCollectorKind,
-    compression_kind: Option<CompressionKind>,
     pixels: Vec<(u16, u16, Color)>,
     max_x: i32,
     max_y: i32,
+    connections: usize,
+    offset: bool,
+    dialect: Dialect,
+    lowercase_hex: bool,
 }
 
 impl From<CodecData> for PixelCollector {
     fn from(codec: CodecData) -> Self {
         Self {
-            kind: if codec.options.binary_px {
-                PixelCollectorKind::Binary
-            } else {
-                PixelCollectorKind::Text
+            kind: match codec.options.dialect.binary {
+                Some(layout) if codec.options.binary_px => PixelCollectorKind::Binary(layout),
+                _ => PixelCollectorKind::Text,
             },
-            compression_kind: codec.options.compression_kind.clone(),
             pixels: Vec::new(),
             max_x: codec.window.get_x() as i32,
             max_y: codec.window.get_y() as i32,
+            connections: codec.options.connections.max(1),
+            offset: codec.options.offset,
+            dialect: codec.options.dialect,
+            lowercase_hex: codec.options.lowercase_hex,
         }
     }
 }
@@ -111,15 +79,45 @@ impl PixelCollector {
         }
     }
 
-    pub fn into_bytes(mut self) -> (usize, Vec<u8>) {
-        self.pixels.sort_unstable_by(|c1, c2| c1.2.cmp(&c2.2));
+    /// Encode the collected pixels into a frame. Every row of pixels is
+    /// assigned to a single connection, so that a pixel is always sent over
+    /// the same connection.
+    pub fn into_frame(mut self) -> Frame {
+        let pixels = self.pixels.len();
+        let mut chunk_pixels = vec![Vec::new(); self.connections];
+        for pixel in self.pixels.drain(..) {
+            chunk_pixels[pixel.1 as usize % self.connections].push(pixel);
+        }
+
+        let chunks = chunk_pixels
+            .into_iter()
+            .map(|pixels| self.encode(pixels))
+            .collect();
+
+        Frame { chunks, pixels }
+    }
+
+    fn encode(&self, mut pixels: Vec<(u16, u16, Color)>) -> Vec<u8> {
+        pixels.sort_unstable_by_key(|c| c.2);
 
-        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        let mut data = Vec::with_capacity(pixels.len() * 4);
 
-        for (x, y, color) in self.pixels.iter() {
+        // The offset stays in effect for everything sent after it, so every
+        // chunk sets it, even if it doesn't need one
+        let (offset_x, offset_y) = match self.kind {
+            PixelCollectorKind::Text if self.offset && !pixels.is_empty() => {
+                let offset_x = pixels.iter().map(|p| p.0).min().unwrap_or(0);
+                let offset_y = pixels.iter().map(|p| p.1).min().unwrap_or(0);
+                data.extend_from_slice(format!("OFFSET {} {}\n", offset_x, offset_y).as_bytes());
+                (offset_x, offset_y)
+            }
+            _ => (0, 0),
+        };
+
+        for (x, y, color) in pixels.iter() {
             match self.kind {
-                PixelCollectorKind::Binary => {
-                    data.extend_from_slice(b"PB");
+                PixelCollectorKind::Binary(layout) => {
+                    data.extend_from_slice(&layout.opcode);
 
                     x.to_le_bytes().iter().for_each(|b| data.push(*b));
                     y.to_le_bytes().iter().for_each(|b| data.push(*b));
@@ -132,13 +130,10 @@ impl PixelCollector {
                 PixelCollectorKind::Text => {
                     data.extend_from_slice(
                         format!(
-                            "PX {} {} {:02X}{:02X}{:02X}{:02X}\n",
-                            x,
-                            y,
-                            color.r,
-                            color.g,
-                            color.b,
-                            color.a.unwrap_or(0xFF)
+                            "PX {} {} {}\n",
+                            x - offset_x,
+                            y - offset_y,
+                            self.text_color(color)
                         )
                         .as_bytes(),
                     );
@@ -146,13 +141,196 @@ impl PixelCollector {
             }
         }
 
-        match self.compression_kind {
-            Some(comp) => match comp {
-                CompressionKind::Zstd => (data.len(), zstd::encode_all(&data[..], 1).unwrap()),
+        data
+    }
+
+    /// Format `color` in the shortest form that the dialect supports
+    fn text_color(&self, color: &Color) -> String {
+        let Color { r, g, b, a } = *color;
+        let a = a.unwrap_or(0xFF);
+        let opaque = a == 0xFF || !self.dialect.alpha;
+
+        let bytes: &[u8] = if opaque && self.dialect.grayscale && r == g && g == b {
+            &[r]
+        } else if opaque {
+            &[r, g, b]
+        } else {
+            &[r, g, b, a]
+        };
+
+        bytes
+            .iter()
+            .map(|byte| {
+                if self.lowercase_hex {
+                    format!("{:02x}", byte)
+                } else {
+                    format!("{:02X}", byte)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{capabilities::Capabilities, codec::CodecOptions, window::Window};
+
+    fn collector(dialect: Dialect, binary_px: bool, lowercase_hex: bool) -> PixelCollector {
+        CodecData {
+            window: Window {
+                x_width: 100,
+                y_height: 100,
             },
-            None => {
-                log::debug!("Not compressing data");
-                (data.len(), data)
+            options: CodecOptions {
+                binary_px,
+                dialect,
+                lowercase_hex,
+                probe_capabilities: false,
+                ..CodecOptions::default()
+            },
+            capabilities: Capabilities::unknown(),
+        }
+        .into()
+    }
+
+    fn encode(mut collector: PixelCollector, color: Color) -> Vec<u8> {
+        collector.add_pixel_colored(12, 3, &color);
+        collector.into_frame().chunks.concat()
+    }
+
+    #[test]
+    fn opaque_colors_omit_alpha() {
+        let collector = collector(Dialect::GENERIC, false, false);
+        let color = Color::from_rgba(0xFF, 0x00, 0xAA, Some(0xFF));
+        assert_eq!(encode(collector, color), b"PX 12 3 FF00AA\n");
+    }
+
+    #[test]
+    fn translucent_colors_keep_alpha() {
+        let collector = collector(Dialect::GENERIC, false, false);
+        let color = Color::from_rgba(0xFF, 0x00, 0xAA, Some(0x80));
+        assert_eq!(encode(collector, color), b"PX 12 3 FF00AA80\n");
+    }
+
+    #[test]
+    fn transparent_pixels_are_skipped() {
+        let collector = collector(Dialect::GENERIC, false, false);
+        let color = Color::from_rgba(0xFF, 0x00, 0xAA, Some(0x00));
+        assert_eq!(encode(collector, color), b"");
+    }
+
+    #[test]
+    fn lowercase_hex() {
+        let collector = collector(Dialect::GENERIC, false, true);
+        let color = Color::from_rgba(0xFF, 0x00, 0xAA, Some(0x80));
+        assert_eq!(encode(collector, color), b"PX 12 3 ff00aa80\n");
+    }
+
+    #[test]
+    fn grayscale_shorthand() {
+        let gray = Color::from_rgba(0x7F, 0x7F, 0x7F, Some(0xFF));
+
+        let breakwater = collector(Dialect::BREAKWATER, false, false);
+        assert_eq!(encode(breakwater, gray), b"PX 12 3 7F\n");
+
+        let generic = collector(Dialect::GENERIC, false, false);
+        assert_eq!(encode(generic, gray), b"PX 12 3 7F7F7F\n");
+    }
+
+    #[test]
+    fn translucent_gray_keeps_alpha() {
+        let collector = collector(Dialect::PIXELNUKE, false, false);
+        let color = Color::from_rgba(0x7F, 0x7F, 0x7F, Some(0x10));
+        assert_eq!(encode(collector, color), b"PX 12 3 7F7F7F10\n");
+    }
+
+    #[test]
+    fn alpha_is_dropped_without_blending() {
+        let collector = collector(Dialect::SHORELINE, false, false);
+        let color = Color::from_rgba(0xFF, 0x00, 0xAA, Some(0x80));
+        assert_eq!(encode(collector, color), b"PX 12 3 FF00AA\n");
+    }
+
+    #[test]
+    fn binary() {
+        let collector = collector(Dialect::GENERIC, true, false);
+        let color = Color::from_rgba(0xFF, 0x00, 0xAA, Some(0xFF));
+        assert_eq!(
+            encode(collector, color),
+            [b'P', b'B', 12, 0, 3, 0, 0xFF, 0x00, 0xAA, 0xFF]
+        );
+    }
+
+    #[test]
+    fn bytes_per_dialect() {
+        let colors = [
+            Color::from_rgba(0xFF, 0x00, 0xAA, Some(0xFF)),
+            Color::from_rgba(0xFF, 0x00, 0xAA, Some(0x80)),
+            Color::from_rgba(0x7F, 0x7F, 0x7F, Some(0xFF)),
+        ];
+        let text: [(Dialect, [&[u8]; 3]); 4] = [
+            (
+                Dialect::GENERIC,
+                [
+                    b"PX 12 3 FF00AA\n",
+                    b"PX 12 3 FF00AA80\n",
+                    b"PX 12 3 7F7F7F\n",
+                ],
+            ),
+            (
+                Dialect::BREAKWATER,
+                [b"PX 12 3 FF00AA\n", b"PX 12 3 FF00AA80\n", b"PX 12 3 7F\n"],
+            ),
+            (
+                Dialect::SHORELINE,
+                [
+                    b"PX 12 3 FF00AA\n",
+                    b"PX 12 3 FF00AA\n",
+                    b"PX 12 3 7F7F7F\n",
+                ],
+            ),
+            (
+                Dialect::PIXELNUKE,
+                [b"PX 12 3 FF00AA\n", b"PX 12 3 FF00AA80\n", b"PX 12 3 7F\n"],
+            ),
+        ];
+
+        for (dialect, expected) in text.iter() {
+            for (color, expected) in colors.iter().zip(expected.iter()) {
+                assert_eq!(
+                    encode(collector(*dialect, false, false), *color),
+                    *expected,
+                    "{}",
+                    dialect
+                );
+
+                // Dialects without a binary protocol fall back to text
+                if dialect.binary.is_none() {
+                    assert_eq!(
+                        encode(collector(*dialect, true, false), *color),
+                        *expected,
+                        "{}",
+                        dialect
+                    );
+                }
+            }
+        }
+
+        let binary: [&[u8]; 3] = [
+            &[b'P', b'B', 12, 0, 3, 0, 0xFF, 0x00, 0xAA, 0xFF],
+            &[b'P', b'B', 12, 0, 3, 0, 0xFF, 0x00, 0xAA, 0x80],
+            &[b'P', b'B', 12, 0, 3, 0, 0x7F, 0x7F, 0x7F, 0xFF],
+        ];
+
+        for dialect in [Dialect::GENERIC, Dialect::BREAKWATER].iter() {
+            for (color, expected) in colors.iter().zip(binary.iter()) {
+                assert_eq!(
+                    encode(collector(*dialect, true, false), *color),
+                    *expected,
+                    "{}",
+                    dialect
+                );
             }
         }
     }
@@ -0,0 +1,272 @@
+use std::{
+    num::NonZeroU64,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::dialect::BinaryLayout;
+
+/// How much of a second of data is sent at once when rate limiting
+const PIECE_FRACTION: f64 = 0.05;
+
+/// How long a limit may be exceeded for, after a period of not sending anything
+const BURST: f64 = 0.1;
+
+/// A token bucket that may go into debt: taking more tokens than are
+/// available is allowed, but has to be paid back before the next take.
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_update: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: NonZeroU64) -> Self {
+        Self {
+            rate: rate.get() as f64,
+            tokens: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate * BURST);
+        self.last_update = now;
+    }
+
+    /// How long to wait until the bucket is out of debt
+    fn wait_time(&mut self) -> Duration {
+        self.refill();
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    fn take(&mut self, amount: usize) {
+        self.refill();
+        self.tokens -= amount as f64;
+    }
+
+    /// The amount of tokens to send at once
+    fn piece_size(&self) -> usize {
+        ((self.rate * PIECE_FRACTION) as usize).max(1)
+    }
+}
+
+/// Limits the amount of bytes and pixels that are sent per second, across
+/// all connections.
+pub struct RateLimiter {
+    bandwidth: Option<Mutex<TokenBucket>>,
+    pixels: Option<Mutex<TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter, or `None` if there is nothing to limit
+    pub fn new(
+        max_bandwidth: Option<NonZeroU64>,
+        max_pixels_per_second: Option<NonZeroU64>,
+    ) -> Option<Self> {
+        if max_bandwidth.is_none() && max_pixels_per_second.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            bandwidth: max_bandwidth.map(|rate| Mutex::new(TokenBucket::new(rate))),
+            pixels: max_pixels_per_second.map(|rate| Mutex::new(TokenBucket::new(rate))),
+        })
+    }
+
+    /// Sleep until sending more data is allowed
+    pub fn wait(&self) {
+        for bucket in self.bandwidth.iter().chain(self.pixels.iter()) {
+            let wait_time = bucket.lock().unwrap().wait_time();
+            std::thread::sleep(wait_time);
+        }
+    }
+
+    /// Record that `bytes` bytes containing `pixels` pixels were sent
+    pub fn sent(&self, bytes: usize, pixels: usize) {
+        if let Some(bandwidth) = &self.bandwidth {
+            bandwidth.lock().unwrap().take(bytes);
+        }
+        if let Some(pixel_rate) = &self.pixels {
+            pixel_rate.lock().unwrap().take(pixels);
+        }
+    }
+
+    /// Split `chunk` into pieces that are small enough to be sent at once.
+    /// Pieces never split a command, and are returned along with the amount
+    /// of pixels in them.
+    pub fn split<'a>(
+        &self,
+        chunk: &'a [u8],
+        binary: Option<BinaryLayout>,
+    ) -> Vec<(&'a [u8], usize)> {
+        let max_bytes = self
+            .bandwidth
+            .as_ref()
+            .map_or(usize::MAX, |bucket| bucket.lock().unwrap().piece_size());
+        let max_pixels = self
+            .pixels
+            .as_ref()
+            .map_or(usize::MAX, |bucket| bucket.lock().unwrap().piece_size());
+
+        match binary {
+            Some(_) => {
+                let command_len = BinaryLayout::COMMAND_LEN;
+                let pixels_per_piece = (max_bytes / command_len).clamp(1, max_pixels);
+                chunk
+                    .chunks(pixels_per_piece * command_len)
+                    .map(|piece| (piece, piece.len() / command_len))
+                    .collect()
+            }
+            None => split_text(chunk, max_bytes, max_pixels),
+        }
+    }
+}
+
+/// Split text commands into pieces of at most `max_bytes` bytes or
+/// `max_pixels` `PX` commands, unless a single command is longer
+fn split_text(chunk: &[u8], max_bytes: usize, max_pixels: usize) -> Vec<(&[u8], usize)> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut pixels = 0;
+    let mut line_start = 0;
+
+    for (index, byte) in chunk.iter().enumerate() {
+        if *byte != b'\n' {
+            continue;
+        }
+
+        let line = &chunk[line_start..index];
+        let is_pixel = line.starts_with(b"PX");
+        let line_end = index + 1;
+
+        // Start a new piece if this line doesn't fit in the current one
+        if line_start > start
+            && (line_end - start > max_bytes || (is_pixel && pixels == max_pixels))
+        {
+            pieces.push((&chunk[start..line_start], pixels));
+            start = line_start;
+            pixels = 0;
+        }
+
+        if is_pixel {
+            pixels += 1;
+        }
+        line_start = line_end;
+    }
+
+    if start < chunk.len() {
+        pieces.push((&chunk[start..], pixels));
+    }
+
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(rate: u64) -> Option<NonZeroU64> {
+        NonZeroU64::new(rate)
+    }
+
+    fn assert_close(actual: Duration, expected: Duration) {
+        let difference = actual.as_secs_f64() - expected.as_secs_f64();
+        assert!(difference.abs() < 0.01, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn wait_until_out_of_debt() {
+        let mut bucket = TokenBucket::new(NonZeroU64::new(1000).unwrap());
+        assert_close(bucket.wait_time(), Duration::ZERO);
+
+        bucket.take(500);
+        assert_close(bucket.wait_time(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn burst_is_capped() {
+        let mut bucket = TokenBucket::new(NonZeroU64::new(1000).unwrap());
+        bucket.last_update -= Duration::from_secs(10);
+        bucket.refill();
+        assert_eq!(bucket.tokens, 1000.0 * BURST);
+
+        bucket.take(300);
+        assert_close(bucket.wait_time(), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn nothing_to_limit() {
+        assert!(RateLimiter::new(None, None).is_none());
+    }
+
+    #[test]
+    fn split_text_by_pixels() {
+        let chunk = b"OFFSET 1 2\nPX 0 0 FF0000\nPX 1 0 FF0000\nPX 2 0 FF0000\n";
+        assert_eq!(
+            split_text(chunk, usize::MAX, 2),
+            [
+                (&b"OFFSET 1 2\nPX 0 0 FF0000\nPX 1 0 FF0000\n"[..], 2),
+                (&b"PX 2 0 FF0000\n"[..], 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_text_by_bytes() {
+        let chunk = b"PX 0 0 FF0000\nPX 1 0 FF0000\nPX 2 0 FF0000\n";
+        assert_eq!(
+            split_text(chunk, 30, usize::MAX),
+            [
+                (&b"PX 0 0 FF0000\nPX 1 0 FF0000\n"[..], 2),
+                (&b"PX 2 0 FF0000\n"[..], 1),
+            ]
+        );
+
+        // A command that is longer than a piece is not split
+        assert_eq!(
+            split_text(chunk, 5, usize::MAX),
+            [
+                (&b"PX 0 0 FF0000\n"[..], 1),
+                (&b"PX 1 0 FF0000\n"[..], 1),
+                (&b"PX 2 0 FF0000\n"[..], 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_binary() {
+        let layout = BinaryLayout { opcode: *b"PB" };
+        let chunk: Vec<u8> = (0..7u8)
+            .flat_map(|i| [b'P', b'B', i, 0, 0, 0, 0xFF, 0x00, 0x00, 0xFF])
+            .collect();
+
+        // 20 pixels per second are sent in pieces of a single pixel, and
+        // 1000 bytes per second in pieces of 50 bytes
+        let pixels = RateLimiter::new(None, rate(20)).unwrap();
+        let pieces = pixels.split(&chunk, Some(layout));
+        assert_eq!(pieces.len(), 7);
+        assert!(pieces
+            .iter()
+            .all(|(piece, pixels)| piece.len() == 10 && *pixels == 1));
+
+        let bandwidth = RateLimiter::new(rate(1000), None).unwrap();
+        let pieces = bandwidth.split(&chunk, Some(layout));
+        let sizes: Vec<_> = pieces
+            .iter()
+            .map(|(piece, pixels)| (piece.len(), *pixels))
+            .collect();
+        assert_eq!(sizes, [(50, 5), (20, 2)]);
+        let joined: Vec<u8> = pieces
+            .iter()
+            .flat_map(|(piece, _)| piece.to_vec())
+            .collect();
+        assert_eq!(joined, chunk);
+    }
+}
@@ -0,0 +1,290 @@
+//! Recordings of the frames that a codec sent, in the `.pfrec` format.
+//!
+//! A recording starts with the line `PFREC 1`, followed by a header of
+//! `key value` lines that ends with an empty line. The header holds the
+//! options that the frames were encoded with. After it, every frame is
+//! stored as:
+//!
+//! - the time since the recording started, in microseconds, as a `u64`
+//! - the amount of pixels in the frame, as a `u64`
+//! - the amount of chunks, as a `u32`
+//! - every chunk, as its length in a `u32` followed by its bytes
+//!
+//! All integers are little endian. Chunks are stored before compression.
+
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    codec::{CodecData, CodecOptions, Frame},
+    dialect::Dialect,
+    window::Window,
+};
+
+const MAGIC: &str = "PFREC 1";
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The options that the frames in a recording were encoded with
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingHeader {
+    /// The size of the canvas that the frames were made for
+    pub window: Window,
+    /// The amount of connections, which is also the amount of chunks per frame
+    pub connections: usize,
+    pub dialect: Dialect,
+    pub binary_px: bool,
+    pub offset: bool,
+}
+
+impl From<&CodecData> for RecordingHeader {
+    fn from(codec: &CodecData) -> Self {
+        Self {
+            window: codec.window.clone(),
+            connections: codec.options.connections.max(1),
+            dialect: codec.options.dialect,
+            binary_px: codec.options.binary_px,
+            offset: codec.options.offset,
+        }
+    }
+}
+
+impl RecordingHeader {
+    /// Use the options of the recording, so that the recorded frames can be
+    /// sent as they are
+    pub fn apply_to(&self, options: &mut CodecOptions) {
+        options.connections = self.connections;
+        options.dialect = self.dialect;
+        options.binary_px = self.binary_px;
+        options.offset = self.offset;
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{}", MAGIC)?;
+        writeln!(
+            writer,
+            "size {} {}",
+            self.window.get_x(),
+            self.window.get_y()
+        )?;
+        writeln!(writer, "connections {}", self.connections)?;
+        writeln!(writer, "dialect {}", self.dialect)?;
+        writeln!(writer, "binary {}", self.binary_px)?;
+        writeln!(writer, "offset {}", self.offset)?;
+        writeln!(writer)
+    }
+
+    fn read<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid_data("the header is incomplete".to_string()));
+            }
+
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line.to_string());
+        }
+
+        if lines.first().map(String::as_str) != Some(MAGIC) {
+            return Err(invalid_data("not a pixelflut recording".to_string()));
+        }
+
+        let value = |key: &str| -> io::Result<&str> {
+            lines
+                .iter()
+                .find_map(|line| match line.split_once(' ') {
+                    Some((k, value)) if k == key => Some(value),
+                    _ => None,
+                })
+                .ok_or_else(|| invalid_data(format!("the header has no {}", key)))
+        };
+        let parse_error = |key: &str| invalid_data(format!("invalid {} in the header", key));
+
+        let window = match value("size")?.split_once(' ') {
+            Some((x, y)) => Window {
+                x_width: x.parse().map_err(|_| parse_error("size"))?,
+                y_height: y.parse().map_err(|_| parse_error("size"))?,
+            },
+            None => return Err(parse_error("size")),
+        };
+
+        Ok(Self {
+            window,
+            connections: value("connections")?
+                .parse()
+                .map_err(|_| parse_error("connections"))?,
+            dialect: value("dialect")?.parse().map_err(invalid_data)?,
+            binary_px: value("binary")?
+                .parse()
+                .map_err(|_| parse_error("binary"))?,
+            offset: value("offset")?
+                .parse()
+                .map_err(|_| parse_error("offset"))?,
+        })
+    }
+}
+
+/// Writes frames to a recording as they are sent
+pub struct Recorder {
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P, header: &RecordingHeader) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        header.write(&mut writer)?;
+        writer.flush()?;
+
+        Ok(Self {
+            writer,
+            started: Instant::now(),
+        })
+    }
+
+    /// Add `frame`, which started to be sent at `sent_at`. Every frame is
+    /// flushed to the file, so that the recording is complete up to the
+    /// last frame if the program is stopped.
+    pub fn record(&mut self, frame: &Frame, sent_at: Instant) -> io::Result<()> {
+        let timestamp = sent_at.saturating_duration_since(self.started).as_micros() as u64;
+        let len = |len: usize| {
+            u32::try_from(len)
+                .map(u32::to_le_bytes)
+                .map_err(|_| invalid_data("the frame is too large to record".to_string()))
+        };
+
+        self.writer.write_all(&timestamp.to_le_bytes())?;
+        self.writer
+            .write_all(&(frame.pixels as u64).to_le_bytes())?;
+        self.writer.write_all(&len(frame.chunks.len())?)?;
+        for chunk in frame.chunks.iter() {
+            self.writer.write_all(&len(chunk.len())?)?;
+            self.writer.write_all(chunk)?;
+        }
+
+        self.writer.flush()
+    }
+}
+
+/// Reads the frames of a recording one by one
+pub struct RecordingReader {
+    reader: BufReader<File>,
+    header: RecordingHeader,
+}
+
+impl RecordingReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header = RecordingHeader::read(&mut reader)?;
+        Ok(Self { reader, header })
+    }
+
+    pub fn header(&self) -> &RecordingHeader {
+        &self.header
+    }
+
+    /// Read the next frame, along with the time since the recording
+    /// started at which it was sent. Returns `None` at the end of the
+    /// recording.
+    pub fn next_frame(&mut self) -> io::Result<Option<(Duration, Frame)>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        let timestamp = Duration::from_micros(self.read_u64()?);
+        let pixels = self.read_u64()? as usize;
+        let chunk_count = self.read_u32()?;
+
+        let mut chunks = Vec::with_capacity(chunk_count as usize);
+        for _ in 0..chunk_count {
+            let len = self.read_u32()?;
+            let mut chunk = Vec::new();
+            (&mut self.reader)
+                .take(len as u64)
+                .read_to_end(&mut chunk)?;
+            if chunk.len() != len as usize {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            chunks.push(chunk);
+        }
+
+        Ok(Some((timestamp, Frame { chunks, pixels })))
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "pixelflut-filler-{}-recording.pfrec",
+            std::process::id()
+        ));
+        let header = RecordingHeader {
+            window: Window {
+                x_width: 800,
+                y_height: 600,
+            },
+            connections: 2,
+            dialect: Dialect::BREAKWATER,
+            binary_px: false,
+            offset: true,
+        };
+        let frames = [
+            Frame {
+                chunks: vec![b"PX 1 2 FF\n".to_vec(), Vec::new()],
+                pixels: 1,
+            },
+            Frame {
+                chunks: vec![b"PX 3 4 00\n".to_vec(), b"PX 5 6 7F\n".to_vec()],
+                pixels: 2,
+            },
+        ];
+
+        let started = Instant::now();
+        let mut recorder = Recorder::create(&path, &header).unwrap();
+        recorder.record(&frames[0], started).unwrap();
+        recorder
+            .record(&frames[1], Instant::now() + Duration::from_millis(100))
+            .unwrap();
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.header(), &header);
+
+        let (first, frame) = reader.next_frame().unwrap().unwrap();
+        assert_eq!(first, Duration::ZERO);
+        assert_eq!((frame.chunks, frame.pixels), (frames[0].chunks.clone(), 1));
+
+        let (second, frame) = reader.next_frame().unwrap().unwrap();
+        assert!(second >= Duration::from_millis(100));
+        assert_eq!((frame.chunks, frame.pixels), (frames[1].chunks.clone(), 2));
+
+        assert!(reader.next_frame().unwrap().is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+}
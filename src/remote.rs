@@ -0,0 +1,207 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    net::TcpStream,
+    str::FromStr,
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::PathBuf};
+
+use crate::connection::{Connector, Socket};
+
+pub const DEFAULT_PORT: u16 = 1337;
+
+/// A pixelflut server to connect to.
+///
+/// Parsed from `host`, `host:port`, `[ipv6]`, `[ipv6]:port`, a bare
+/// IPv6 address, or `unix:/path/to/socket`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Remote {
+    Tcp {
+        host: String,
+        port: u16,
+    },
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for Remote {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(Self::Unix(PathBuf::from(path)));
+            #[cfg(not(unix))]
+            return Err(format!(
+                "Unix sockets are not supported on this platform: {}",
+                path
+            ));
+        }
+
+        let parse_port = |port: &str| {
+            port.parse::<u16>()
+                .map_err(|_| format!("Invalid port {}", port))
+        };
+
+        let (host, port) = if let Some(rest) = s.strip_prefix('[') {
+            let (host, rest) = rest
+                .split_once(']')
+                .ok_or_else(|| format!("Missing closing bracket in {}", s))?;
+
+            let port = match rest {
+                "" => DEFAULT_PORT,
+                rest => match rest.strip_prefix(':') {
+                    Some(port) => parse_port(port)?,
+                    None => return Err(format!("Unexpected {} after IPv6 address", rest)),
+                },
+            };
+
+            (host, port)
+        } else {
+            match s.split_once(':') {
+                // More than one colon means this is an IPv6 address without a port
+                Some((_, rest)) if rest.contains(':') => (s, DEFAULT_PORT),
+                Some((host, port)) => (host, parse_port(port)?),
+                None => (s, DEFAULT_PORT),
+            }
+        };
+
+        if host.is_empty() {
+            return Err(format!("Missing host in {}", s));
+        }
+
+        Ok(Self::Tcp {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl Display for Remote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Remote::Tcp { host, port } if host.contains(':') => write!(f, "[{}]:{}", host, port),
+            Remote::Tcp { host, port } => write!(f, "{}:{}", host, port),
+            #[cfg(unix)]
+            Remote::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl Connector for Remote {
+    type Socket = Stream;
+
+    fn connect(&self) -> std::io::Result<Stream> {
+        match self {
+            Remote::Tcp { host, port } => {
+                TcpStream::connect((host.as_str(), *port)).map(Stream::Tcp)
+            }
+            #[cfg(unix)]
+            Remote::Unix(path) => UnixStream::connect(path).map(Stream::Unix),
+        }
+    }
+}
+
+/// A connection to a [`Remote`]
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+impl Socket for Stream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tcp(host: &str, port: u16) -> Result<Remote, String> {
+        Ok(Remote::Tcp {
+            host: host.to_string(),
+            port,
+        })
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("localhost".parse(), tcp("localhost", DEFAULT_PORT));
+        assert_eq!("10.0.0.1:1234".parse(), tcp("10.0.0.1", 1234));
+        assert_eq!("[::1]:1234".parse(), tcp("::1", 1234));
+        assert_eq!("[::1]".parse(), tcp("::1", DEFAULT_PORT));
+        assert_eq!("fe80::1".parse(), tcp("fe80::1", DEFAULT_PORT));
+        #[cfg(unix)]
+        assert_eq!(
+            "unix:/tmp/pixelflut.sock".parse(),
+            Ok(Remote::Unix(PathBuf::from("/tmp/pixelflut.sock")))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for remote in [
+            "",
+            ":1234",
+            "[]:1234",
+            "host:",
+            "host:port",
+            "host:65536",
+            "[::1]:x",
+            "[::1",
+            "[::1]1234",
+        ]
+        .iter()
+        {
+            assert!(
+                remote.parse::<Remote>().is_err(),
+                "{} should not parse",
+                remote
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for remote in ["localhost:1337", "[::1]:1234"].iter() {
+            assert_eq!(remote.parse::<Remote>().unwrap().to_string(), *remote);
+        }
+    }
+}
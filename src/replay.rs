@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use crate::{
+    codec::{CodecData, DataProducer, Frame, RunError},
+    recording::RecordingReader,
+};
+
+/// Sends the frames of a recording, with the time between them that they
+/// were recorded with, divided by `speed`
+pub struct Replay {
+    reader: RecordingReader,
+    speed: f64,
+    /// The next frame to send, and the time it was recorded at
+    next: Option<(Duration, Frame)>,
+}
+
+impl Replay {
+    pub fn new(reader: RecordingReader, speed: f64) -> Self {
+        Self {
+            reader,
+            speed,
+            next: None,
+        }
+    }
+}
+
+impl DataProducer for Replay {
+    fn do_setup(&mut self, codec: &CodecData) -> Result<(), String> {
+        if !(self.speed > 0.0 && self.speed.is_finite()) {
+            return Err(format!("Invalid replay speed {}", self.speed));
+        }
+
+        let header = self.reader.header();
+        let options = &codec.options;
+
+        // The frames are sent as they were recorded, so the server has to
+        // understand the commands they were encoded with
+        if header.binary_px && !options.binary_px {
+            return Err("The recording uses the binary protocol".to_string());
+        }
+        if header.offset && !options.offset {
+            return Err("The recording uses OFFSET".to_string());
+        }
+
+        if header.window != codec.window {
+            log::warn!(
+                "The recording was made for a {}x{} canvas, but the canvas is {}x{}",
+                header.window.get_x(),
+                header.window.get_y(),
+                codec.window.get_x(),
+                codec.window.get_y()
+            );
+        }
+
+        if self.next.is_none() {
+            self.next = self
+                .reader
+                .next_frame()
+                .map_err(|e| format!("Could not read the recording: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
+        let (timestamp, frame) = match self.next.take() {
+            Some(next) => next,
+            None => return Ok((Frame::default(), None)),
+        };
+
+        self.next = self.reader.next_frame()?;
+
+        let delay = self
+            .next
+            .as_ref()
+            .map(|(next, _)| next.saturating_sub(timestamp).div_f64(self.speed));
+
+        Ok((frame, delay))
+    }
+
+    /// The recorded frames may only contain changes, like those of `snake`
+    fn incremental(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,77 @@
+use std::{path::PathBuf, time::Duration};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    canvas::{Canvas, Region},
+    codec::{CodecData, DataProducer, Frame, RunError},
+};
+
+/// Reads the canvas back from the server once, and saves it as an image
+pub struct Screenshot {
+    path: PathBuf,
+    region: Option<Region>,
+    result: Result<(), String>,
+}
+
+impl Screenshot {
+    pub fn new(path: PathBuf, region: Option<Region>) -> Self {
+        Self {
+            path,
+            region,
+            result: Ok(()),
+        }
+    }
+}
+
+impl DataProducer for Screenshot {
+    fn do_setup(&mut self, codec: &CodecData) -> Result<(), String> {
+        let region = self
+            .region
+            .unwrap_or_else(|| Region::full(&codec.window))
+            .clamp(&codec.window);
+
+        if region.width == 0 || region.height == 0 {
+            return Err(format!(
+                "{:?} is outside of the canvas",
+                self.region.unwrap_or(region)
+            ));
+        }
+
+        self.region = Some(region);
+        Ok(())
+    }
+
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
+        std::mem::replace(&mut self.result, Ok(())).map_err(RunError::DataProducer)?;
+        Ok((Frame::default(), None))
+    }
+
+    fn readback_region(&self) -> Option<Region> {
+        self.region
+    }
+
+    fn update_canvas(&mut self, canvas: &Canvas) {
+        let region = canvas.region();
+
+        let image = RgbaImage::from_fn(region.width as u32, region.height as u32, |x, y| {
+            let color = canvas
+                .get(region.x + x as usize, region.y + y as usize)
+                .unwrap_or_default();
+            Rgba([color.r, color.g, color.b, color.a.unwrap_or(0xFF)])
+        });
+
+        self.result = image
+            .save(&self.path)
+            .map_err(|e| format!("Could not save {}: {}", self.path.display(), e));
+
+        if self.result.is_ok() {
+            log::info!(
+                "Saved {}x{} screenshot to {}",
+                region.width,
+                region.height,
+                self.path.display()
+            );
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use crate::{
-    codec::{CodecData, DataProducer, RunError},
+    codec::{CodecData, DataProducer, Frame, RunError},
     color::Color,
     pixelcollector::PixelCollector,
 };
@@ -68,8 +68,8 @@ impl Snake {
     fn advance(&mut self) {
         let (x, y) = self.direction.get_delta_p();
 
-        self.x += x as isize;
-        self.y += y as isize;
+        self.x += x;
+        self.y += y;
 
         let width = self.codec_data.as_ref().unwrap().window.get_y() as isize;
         let height = self.codec_data.as_ref().unwrap().window.get_x() as isize;
@@ -92,13 +92,24 @@ impl Snake {
     }
 }
 
+impl Default for Snake {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DataProducer for Snake {
     fn do_setup(&mut self, codec: &crate::codec::CodecData) -> Result<(), String> {
         self.codec_data = Some(codec.clone());
         Ok(())
     }
 
-    fn get_next_data(&mut self) -> Result<(Vec<u8>, Option<Duration>), RunError> {
+    /// Every frame only draws the part of the snake that moved
+    fn incremental(&self) -> bool {
+        true
+    }
+
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
         let tail = (self.x, self.y);
         self.advance();
 
@@ -122,8 +133,6 @@ impl DataProducer for Snake {
             }
         }
 
-        let (_bytes, data) = pixel_collector.into_bytes();
-
-        Ok((data, Some(Duration::from_millis(1))))
+        Ok((pixel_collector.into_frame(), Some(Duration::from_millis(1))))
     }
 }
@@ -0,0 +1,286 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Default)]
+struct Counters {
+    /// Bytes written to the sockets, after compression
+    bytes_sent: u64,
+    /// Bytes of pixel data, before compression
+    bytes_uncompressed: u64,
+    pixels: u64,
+    frames: u64,
+    /// Frames that were dropped from the queue before they were sent
+    frames_dropped: u64,
+    write_time: Duration,
+    sleep_time: Duration,
+}
+
+impl Counters {
+    fn compression_ratio(&self) -> f64 {
+        if self.bytes_sent == 0 {
+            1.0
+        } else {
+            self.bytes_uncompressed as f64 / self.bytes_sent as f64
+        }
+    }
+}
+
+/// Throughput statistics for a codec, which are logged and written to a
+/// Prometheus text file periodically
+pub struct Stats {
+    interval: Duration,
+    prometheus_file: Option<PathBuf>,
+    started: Instant,
+    last_report: Instant,
+    total: Counters,
+    since_report: Counters,
+}
+
+impl Stats {
+    pub fn new(interval: Duration, prometheus_file: Option<PathBuf>) -> Self {
+        let now = Instant::now();
+        Self {
+            interval,
+            prometheus_file,
+            started: now,
+            last_report: now,
+            total: Counters::default(),
+            since_report: Counters::default(),
+        }
+    }
+
+    fn update(&mut self, update: impl Fn(&mut Counters)) {
+        update(&mut self.total);
+        update(&mut self.since_report);
+    }
+
+    /// Record that a frame was written
+    pub fn frame_sent(
+        &mut self,
+        bytes_sent: usize,
+        bytes_uncompressed: usize,
+        pixels: usize,
+        write_time: Duration,
+    ) {
+        self.update(|counters| {
+            counters.bytes_sent += bytes_sent as u64;
+            counters.bytes_uncompressed += bytes_uncompressed as u64;
+            counters.pixels += pixels as u64;
+            counters.frames += 1;
+            counters.write_time += write_time;
+        });
+    }
+
+    pub fn frames_dropped(&mut self, frames: u64) {
+        self.update(|counters| counters.frames_dropped += frames);
+    }
+
+    pub fn slept(&mut self, sleep_time: Duration) {
+        self.update(|counters| counters.sleep_time += sleep_time);
+    }
+
+    /// Report the statistics if the report interval has passed
+    pub fn report_if_due(&mut self) {
+        if self.last_report.elapsed() >= self.interval {
+            self.report();
+        }
+    }
+
+    /// Log the statistics since the last report, and write the totals to
+    /// the Prometheus file if there is one.
+    pub fn report(&mut self) {
+        let elapsed = self.last_report.elapsed().as_secs_f64();
+        let counters = &self.since_report;
+        let busy = (counters.write_time + counters.sleep_time).as_secs_f64();
+        let percentage = |time: Duration| {
+            if busy > 0.0 {
+                time.as_secs_f64() / busy * 100.0
+            } else {
+                0.0
+            }
+        };
+
+        log::info!(
+            "{} frames ({:.1} fps, {} dropped), {} pixels ({:.0}/s), {} bytes ({:.0}/s), \
+            compression ratio {:.2}, writing {:.0}% sleeping {:.0}%",
+            counters.frames,
+            counters.frames as f64 / elapsed,
+            counters.frames_dropped,
+            counters.pixels,
+            counters.pixels as f64 / elapsed,
+            counters.bytes_sent,
+            counters.bytes_sent as f64 / elapsed,
+            counters.compression_ratio(),
+            percentage(counters.write_time),
+            percentage(counters.sleep_time),
+        );
+
+        if let Some(path) = &self.prometheus_file {
+            let metrics = self.prometheus(counters.frames as f64 / elapsed);
+            // Write to a temporary file first, so that scrapers never see a partial file
+            let temporary = temporary_path(path);
+            if let Err(e) =
+                std::fs::write(&temporary, metrics).and_then(|_| std::fs::rename(&temporary, path))
+            {
+                log::warn!("Could not write statistics to {}: {}", path.display(), e);
+            }
+        }
+
+        self.last_report = Instant::now();
+        self.since_report = Counters::default();
+    }
+
+    /// The totals, in the Prometheus text format
+    fn prometheus(&self, fps: f64) -> String {
+        let total = &self.total;
+        let metrics: [(&str, &str, &str, f64); 10] = [
+            (
+                "pixelflut_bytes_sent_total",
+                "counter",
+                "Bytes written to the server, after compression",
+                total.bytes_sent as f64,
+            ),
+            (
+                "pixelflut_uncompressed_bytes_total",
+                "counter",
+                "Bytes of pixel data, before compression",
+                total.bytes_uncompressed as f64,
+            ),
+            (
+                "pixelflut_pixels_sent_total",
+                "counter",
+                "Pixels sent to the server",
+                total.pixels as f64,
+            ),
+            (
+                "pixelflut_frames_sent_total",
+                "counter",
+                "Frames sent to the server",
+                total.frames as f64,
+            ),
+            (
+                "pixelflut_frames_dropped_total",
+                "counter",
+                "Frames dropped from the queue before they were sent",
+                total.frames_dropped as f64,
+            ),
+            (
+                "pixelflut_write_seconds_total",
+                "counter",
+                "Time spent writing frames",
+                total.write_time.as_secs_f64(),
+            ),
+            (
+                "pixelflut_sleep_seconds_total",
+                "counter",
+                "Time spent waiting for the next frame",
+                total.sleep_time.as_secs_f64(),
+            ),
+            (
+                "pixelflut_uptime_seconds",
+                "gauge",
+                "Time since the statistics started",
+                self.started.elapsed().as_secs_f64(),
+            ),
+            (
+                "pixelflut_frames_per_second",
+                "gauge",
+                "Frames sent per second since the last report",
+                fps,
+            ),
+            (
+                "pixelflut_compression_ratio",
+                "gauge",
+                "Uncompressed bytes per byte sent",
+                total.compression_ratio(),
+            ),
+        ];
+
+        let mut output = String::new();
+        for (name, kind, help, value) in metrics.iter() {
+            writeln!(output, "# HELP {} {}", name, help).unwrap();
+            writeln!(output, "# TYPE {} {}", name, kind).unwrap();
+            writeln!(output, "{} {}", name, value).unwrap();
+        }
+        output
+    }
+}
+
+/// The path to write `path` to before moving it into place: the same
+/// path, with `.tmp` appended to the file name
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    temporary.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temporary_path_appends_to_the_file_name() {
+        assert_eq!(
+            temporary_path(Path::new("/var/lib/stats.prom")),
+            Path::new("/var/lib/stats.prom.tmp")
+        );
+        assert_eq!(
+            temporary_path(Path::new("stats.tmp")),
+            Path::new("stats.tmp.tmp")
+        );
+        assert_eq!(temporary_path(Path::new("stats")), Path::new("stats.tmp"));
+    }
+
+    #[test]
+    fn prometheus_format() {
+        let mut stats = Stats::new(Duration::from_secs(10), None);
+        stats.frame_sent(100, 400, 40, Duration::from_millis(250));
+        stats.frame_sent(100, 400, 40, Duration::from_millis(250));
+        stats.frames_dropped(3);
+
+        let output = stats.prometheus(2.5);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 30);
+
+        // Every metric has a HELP and a TYPE line before its value
+        for metric in lines.chunks(3) {
+            let name = metric[2].split(' ').next().unwrap();
+            assert!(name.starts_with("pixelflut_"), "{}", name);
+            assert!(metric[0].starts_with(&format!("# HELP {} ", name)));
+            let kind = metric[1]
+                .strip_prefix(&format!("# TYPE {} ", name))
+                .unwrap();
+            assert_eq!(name.ends_with("_total"), kind == "counter", "{}", name);
+            assert!(kind == "counter" || kind == "gauge", "{}", kind);
+        }
+
+        for expected in [
+            "pixelflut_bytes_sent_total 200",
+            "pixelflut_uncompressed_bytes_total 800",
+            "pixelflut_pixels_sent_total 80",
+            "pixelflut_frames_sent_total 2",
+            "pixelflut_frames_dropped_total 3",
+            "pixelflut_write_seconds_total 0.5",
+            "pixelflut_frames_per_second 2.5",
+            "pixelflut_compression_ratio 4",
+        ] {
+            assert!(lines.contains(&expected), "{} is missing", expected);
+        }
+    }
+
+    #[test]
+    fn report_writes_the_file() {
+        let path = std::env::temp_dir().join(format!("pixelflut-stats-{}.tmp", std::process::id()));
+        let mut stats = Stats::new(Duration::from_secs(10), Some(path.clone()));
+        stats.frame_sent(100, 100, 10, Duration::from_millis(1));
+        stats.report();
+
+        let output = std::fs::read_to_string(&path).unwrap();
+        assert!(output.contains("pixelflut_frames_sent_total 1\n"));
+        assert!(!temporary_path(&path).exists());
+        std::fs::remove_file(path).unwrap();
+    }
+}
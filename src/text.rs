@@ -0,0 +1,144 @@
+use std::{path::PathBuf, time::Duration};
+
+use rand::{thread_rng, Rng};
+
+use crate::{
+    codec::{CodecData, DataProducer, Frame, RunError},
+    color::Color,
+    fill::Fill,
+    letters::{Font, LetterString, LETTER_HEIGHT},
+    pixelcollector::PixelCollector,
+};
+
+pub struct Text {
+    text: String,
+    font_path: Option<PathBuf>,
+    font_size: Option<f32>,
+    scale: usize,
+    x: Option<usize>,
+    y: Option<usize>,
+    color: Option<Color>,
+    count: usize,
+    fill: Option<Fill>,
+    data: Frame,
+}
+
+impl Text {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        text: String,
+        font_path: Option<PathBuf>,
+        font_size: Option<f32>,
+        scale: usize,
+        x: Option<usize>,
+        y: Option<usize>,
+        color: Option<Color>,
+        count: usize,
+        fill_color: Option<Color>,
+    ) -> Self {
+        Self {
+            text,
+            font_path,
+            font_size,
+            scale,
+            x,
+            y,
+            color,
+            count,
+            fill: fill_color.map(|color| Fill::new(color, false)),
+            data: Frame::default(),
+        }
+    }
+
+    fn letter_color(&self) -> Color {
+        self.color.unwrap_or(Color {
+            a: Some(0xFF),
+            ..Color::random()
+        })
+    }
+}
+
+impl DataProducer for Text {
+    fn do_setup(&mut self, codec: &CodecData) -> Result<(), String> {
+        self.data = Frame::default();
+
+        // The background has to be sent in full before the text, as the pixel
+        // collector reorders pixels by color.
+        if let Some(fill) = self.fill.as_mut() {
+            fill.do_setup(codec)?;
+            let (fill_data, _) = fill.get_next_data().map_err(|e| format!("{:?}", e))?;
+            self.data.append(fill_data);
+        }
+
+        let font = match &self.font_path {
+            Some(path) => {
+                let size = self
+                    .font_size
+                    .unwrap_or((LETTER_HEIGHT * self.scale) as f32);
+                Font::load(path, size).map_err(|e| format!("{:?}", e))?
+            }
+            None => Font::builtin(),
+        };
+
+        // Scalable fonts are rasterized at the requested size, so they
+        // should not be scaled up any further.
+        let scale = if font.is_scalable() { 1 } else { self.scale };
+        let letters = LetterString::new(&font, &self.text);
+        let width = letters.width() * scale;
+        let height = font.height() * scale;
+
+        let (x_max, y_max) = (codec.window.get_x(), codec.window.get_y());
+
+        let mut pixel_collector: PixelCollector = codec.clone().into();
+        for _ in 0..self.count {
+            let x = self
+                .x
+                .unwrap_or_else(|| thread_rng().gen_range(0..=x_max.saturating_sub(width)));
+            let y = self
+                .y
+                .unwrap_or_else(|| thread_rng().gen_range(0..=y_max.saturating_sub(height)));
+
+            let mut pen = x as i32;
+            for glyph in letters.iter() {
+                let color = self.letter_color();
+                let alpha = color.a.unwrap_or(0xFF) as u32;
+                let glyph_x = pen + glyph.left * scale as i32;
+                let glyph_y = y as i32 + glyph.top * scale as i32;
+
+                for row in 0..glyph.height {
+                    for column in 0..glyph.width {
+                        let coverage = glyph.coverage(column, row) as u32;
+                        if coverage == 0 {
+                            continue;
+                        }
+
+                        let color = Color {
+                            a: Some((alpha * coverage / 0xFF) as u8),
+                            ..color
+                        };
+
+                        for dy in 0..scale {
+                            for dx in 0..scale {
+                                pixel_collector.add_pixel_colored(
+                                    glyph_x + (column * scale + dx) as i32,
+                                    glyph_y + (row * scale + dy) as i32,
+                                    &color,
+                                );
+                            }
+                        }
+                    }
+                }
+
+                pen += (glyph.advance * scale) as i32;
+            }
+        }
+
+        self.data.append(pixel_collector.into_frame());
+
+        Ok(())
+    }
+
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
+        Ok((self.data.clone(), None))
+    }
+}
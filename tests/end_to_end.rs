@@ -0,0 +1,551 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    num::NonZeroU64,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use pixelflut_filler::{
+    canvas::Region,
+    codec::{Codec, CodecData, CodecOptions, DataProducer, Frame, ReconnectOptions, RunError},
+    color::Color,
+    compression::CompressionKind,
+    connection::{Connector, Socket},
+    defender::Defender,
+    dialect::Dialect,
+    dryrun::DryRun,
+    fill::Fill,
+    framequeue::QueuePolicy,
+    gif::Gif,
+    image::Image,
+    mockserver::MockServer,
+    recording::RecordingReader,
+    remote::{Remote, Stream},
+    replay::Replay,
+    snake::Snake,
+};
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+
+fn start_server() -> MockServer {
+    MockServer::start("127.0.0.1:0", WIDTH, HEIGHT).unwrap()
+}
+
+fn remote(server: &MockServer) -> Remote {
+    Remote::Tcp {
+        host: "127.0.0.1".to_string(),
+        port: server.address().port(),
+    }
+}
+
+fn options() -> CodecOptions {
+    CodecOptions {
+        stats_interval: Duration::from_secs(60),
+        ..CodecOptions::default()
+    }
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::from_rgba(r, g, b, Some(0xFF))
+}
+
+/// A file in the temporary directory that is unique to this test run
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pixelflut-filler-{}-{}", std::process::id(), name))
+}
+
+/// Wait until `condition` holds for the canvas of `server`. The server
+/// handles commands asynchronously, so pixels can arrive after the codec is
+/// done sending them.
+fn wait_for(server: &MockServer, condition: impl Fn(&MockServer) -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if condition(server) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+fn is_filled(server: &MockServer, color: Color) -> bool {
+    let framebuffer = server.framebuffer();
+    (0..HEIGHT).all(|y| (0..WIDTH).all(|x| framebuffer.get(x, y) == Some(color)))
+}
+
+/// Draw a pixel as another client would, and wait until the server drew it
+fn overwrite(server: &MockServer, x: usize, y: usize, color: Color) {
+    let mut stream = TcpStream::connect(server.address()).unwrap();
+    write!(stream, "PX {} {} {:x}\nPX {} {}\n", x, y, color, x, y).unwrap();
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).unwrap();
+    assert!(reply.starts_with(&format!("PX {} {} ", x, y)), "{}", reply);
+}
+
+fn run<D: DataProducer + Send>(server: &MockServer, producer: D, options: CodecOptions) {
+    Codec::new(remote(server), producer, options)
+        .unwrap()
+        .run()
+        .unwrap();
+}
+
+/// Run a data producer that never finishes in the background, until it
+/// sent `max_frames` frames
+fn run_in_background<D: DataProducer + Send + 'static>(
+    server: &MockServer,
+    producer: D,
+    max_frames: u64,
+) -> JoinHandle<()> {
+    let options = CodecOptions {
+        max_frames: Some(max_frames),
+        ..options()
+    };
+    let codec = Codec::new(remote(server), producer, options).unwrap();
+    std::thread::spawn(move || codec.run().unwrap())
+}
+
+#[test]
+fn fill_text() {
+    let server = start_server();
+    let color = rgb(0x12, 0x34, 0x56);
+
+    run(&server, Fill::new(color, false), options());
+
+    assert!(wait_for(&server, |server| is_filled(server, color)));
+}
+
+#[test]
+fn fill_binary() {
+    let server = start_server();
+    let color = rgb(0xAB, 0xCD, 0xEF);
+
+    let options = CodecOptions {
+        binary_px: true,
+        ..options()
+    };
+    run(&server, Fill::new(color, true), options);
+
+    assert!(wait_for(&server, |server| is_filled(server, color)));
+}
+
+#[test]
+fn fill_compressed_over_multiple_connections() {
+    let server = start_server();
+    let color = rgb(0x80, 0x80, 0x80);
+
+    let options = CodecOptions {
+        compression_kind: Some(CompressionKind::Zstd(3)),
+        dialect: Dialect::BREAKWATER,
+        offset: true,
+        connections: 3,
+        ..options()
+    };
+    run(&server, Fill::new(color, false), options);
+
+    assert!(wait_for(&server, |server| is_filled(server, color)));
+}
+
+#[test]
+fn fill_compressed_with_rate_limit() {
+    let server = start_server();
+    let color = rgb(0x12, 0x9A, 0xBC);
+
+    // The frame is written in many small pieces, and the compressed stream
+    // is only flushed at the end of it
+    let options = CodecOptions {
+        compression_kind: Some(CompressionKind::Zstd(3)),
+        max_pixels_per_second: NonZeroU64::new(20_000),
+        ..options()
+    };
+    run(&server, Fill::new(color, true), options);
+
+    assert!(wait_for(&server, |server| is_filled(server, color)));
+}
+
+#[test]
+fn translucent_fill_is_blended() {
+    let server = start_server();
+
+    run(&server, Fill::new(rgb(0xFF, 0xFF, 0xFF), false), options());
+    let translucent = Color::from_rgba(0x00, 0x00, 0x00, Some(0x80));
+    run(&server, Fill::new(translucent, false), options());
+
+    let blended = rgb(0x7F, 0x7F, 0x7F);
+    assert!(wait_for(&server, |server| is_filled(server, blended)));
+}
+
+#[test]
+fn read_back() {
+    let server = start_server();
+    let color = rgb(0x01, 0x02, 0x03);
+    run(&server, Fill::new(color, false), options());
+    assert!(wait_for(&server, |server| is_filled(server, color)));
+
+    let options = CodecOptions {
+        connections: 2,
+        ..options()
+    };
+    let mut codec = Codec::new(remote(&server), Fill::new(color, false), options).unwrap();
+    let region = Region {
+        x: 10,
+        y: 5,
+        width: 8,
+        height: 4,
+    };
+    let canvas = codec.read_region(region).unwrap();
+
+    for (x, y) in region.coordinates() {
+        assert_eq!(canvas.get(x, y), Some(color), "pixel at {}, {}", x, y);
+    }
+    assert_eq!(canvas.get(9, 5), None);
+}
+
+#[test]
+fn image_with_offset() {
+    let server = start_server();
+
+    let path = temp_file("image.png");
+    let image = image::RgbaImage::from_fn(4, 3, |x, y| {
+        image::Rgba([x as u8 * 50, y as u8 * 50, 0xFF, 0xFF])
+    });
+    image.save(&path).unwrap();
+
+    run(&server, Image::new(path.clone(), None, 10, -5), options());
+    std::fs::remove_file(path).unwrap();
+
+    // A negative offset is from the bottom
+    let top = HEIGHT - 5 - 3;
+    assert!(wait_for(&server, |server| {
+        (0..3).all(|y| {
+            (0..4).all(|x| {
+                server.pixel(10 + x, top + y) == Some(rgb(x as u8 * 50, y as u8 * 50, 0xFF))
+            })
+        })
+    }));
+    assert_eq!(server.pixel(9, top), Some(Color::default()));
+}
+
+#[test]
+fn gif_frames() {
+    let server = start_server();
+
+    let path = temp_file("animation.gif");
+    {
+        let file = std::fs::File::create(&path).unwrap();
+        let palette = [0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00];
+        let mut encoder = gif::Encoder::new(file, 2, 2, &palette).unwrap();
+        for index in 0..2 {
+            let frame = gif::Frame {
+                width: 2,
+                height: 2,
+                buffer: vec![index; 4].into(),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).unwrap();
+        }
+    }
+
+    // Every frame is shown for 50 ms, for about 2 seconds in total
+    let gif = Gif::new(path.clone(), Duration::from_millis(50), 0, 0);
+    let codec = run_in_background(&server, gif, 40);
+
+    for color in [rgb(0xFF, 0, 0), rgb(0, 0xFF, 0)].iter() {
+        assert!(wait_for(&server, |server| server.pixel(1, 1) == Some(*color)));
+    }
+    codec.join().unwrap();
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn snake_draws() {
+    let server = start_server();
+
+    let codec = run_in_background(&server, Snake::new(), 100);
+
+    assert!(wait_for(&server, |server| {
+        let framebuffer = server.framebuffer();
+        (0..HEIGHT).any(|y| (0..WIDTH).any(|x| framebuffer.get(x, y) != Some(Color::default())))
+    }));
+    codec.join().unwrap();
+}
+
+#[test]
+fn record_and_replay() {
+    let color = rgb(0x42, 0x13, 0x37);
+    let path = temp_file("recording.pfrec");
+
+    let recorded = start_server();
+    let recording = CodecOptions {
+        offset: true,
+        connections: 2,
+        record: Some(path.clone()),
+        ..options()
+    };
+    run(&recorded, Fill::new(color, false), recording);
+    assert!(wait_for(&recorded, |server| is_filled(server, color)));
+
+    // The recording decides the protocol options
+    let replayed = start_server();
+    let reader = RecordingReader::open(&path).unwrap();
+    let mut options = options();
+    reader.header().apply_to(&mut options);
+    assert_eq!((options.connections, options.offset), (2, true));
+    run(&replayed, Replay::new(reader, 1.0), options);
+    std::fs::remove_file(path).unwrap();
+
+    assert!(wait_for(&replayed, |server| is_filled(server, color)));
+}
+
+#[test]
+fn dry_run_saves_the_last_frame() {
+    let color = rgb(0x10, 0x20, 0x30);
+    let path = temp_file("dry-run.png");
+
+    let dry_run = DryRun::new(WIDTH, HEIGHT, path.clone()).unwrap();
+    Codec::new(dry_run.clone(), Fill::new(color, false), options())
+        .unwrap()
+        .run()
+        .unwrap();
+    dry_run.finish().unwrap();
+
+    let image = image::open(&path).unwrap().to_rgba8();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(image.dimensions(), (WIDTH as u32, HEIGHT as u32));
+    assert!(image
+        .pixels()
+        .all(|pixel| pixel.0 == [0x10, 0x20, 0x30, 0xFF]));
+}
+
+#[test]
+fn dry_run_saves_an_animation() {
+    let image_path = temp_file("frame.png");
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([0xFF, 0, 0, 0xFF]))
+        .save(&image_path)
+        .unwrap();
+    let path = temp_file("dry-run.gif");
+
+    // Frames that are shown for at least the 10 ms resolution of GIFs are
+    // kept apart. The interval leaves room for the test threads to be late.
+    let image = Image::new(image_path.clone(), Some(Duration::from_millis(50)), 0, 0);
+    let options = CodecOptions {
+        max_frames: Some(5),
+        ..options()
+    };
+    let dry_run = DryRun::new(WIDTH, HEIGHT, path.clone()).unwrap();
+    Codec::new(dry_run.clone(), image, options)
+        .unwrap()
+        .run()
+        .unwrap();
+    dry_run.finish().unwrap();
+    std::fs::remove_file(image_path).unwrap();
+
+    let mut decoder = gif::DecodeOptions::new()
+        .read_info(std::fs::File::open(&path).unwrap())
+        .unwrap();
+    let mut delays = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        delays.push(frame.delay);
+    }
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(delays.len(), 5);
+    assert!(delays.iter().all(|delay| *delay >= 1), "{:?}", delays);
+}
+
+#[test]
+fn dry_run_animation_size_fits_a_gif() {
+    let path = temp_file("too-large.gif");
+    assert!(DryRun::new(70_000, 10, path.clone()).is_err());
+    assert!(!path.exists());
+}
+
+#[test]
+fn reconnects_and_resumes() {
+    let server = start_server();
+    let color = rgb(0x33, 0x66, 0x99);
+
+    let path = temp_file("reconnect.png");
+    image::RgbaImage::from_pixel(
+        WIDTH as u32,
+        HEIGHT as u32,
+        image::Rgba([0x33, 0x66, 0x99, 0xFF]),
+    )
+    .save(&path)
+    .unwrap();
+    let image = Image::new(path.clone(), Some(Duration::from_millis(20)), 0, 0);
+
+    let options = CodecOptions {
+        reconnect: Some(ReconnectOptions {
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(100),
+        }),
+        max_frames: Some(100),
+        ..options()
+    };
+    let codec = Codec::new(remote(&server), image, options).unwrap();
+    let codec = std::thread::spawn(move || codec.run().unwrap());
+    assert!(wait_for(&server, |server| is_filled(server, color)));
+
+    // Only a new connection can repaint the pixel, as the server doesn't
+    // handle anything that is sent over the old one
+    server.disconnect_clients();
+    overwrite(&server, 5, 5, rgb(0xFF, 0, 0));
+    assert!(wait_for(&server, |server| server.pixel(5, 5) == Some(color)));
+
+    codec.join().unwrap();
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn defender_repaints_overwritten_pixels() {
+    let server = start_server();
+    let color = rgb(0x33, 0x66, 0x99);
+
+    // Only every other pixel is defended, the rest is transparent
+    let path = temp_file("defend.png");
+    image::RgbaImage::from_fn(8, 8, |x, y| {
+        let alpha = if (x + y) % 2 == 0 { 0xFF } else { 0x00 };
+        image::Rgba([0x33, 0x66, 0x99, alpha])
+    })
+    .save(&path)
+    .unwrap();
+    let image = Image::new(path.clone(), None, 10, 5);
+    let defender = Defender::new(image, Duration::from_millis(20));
+
+    let codec = run_in_background(&server, defender, 100);
+    assert!(wait_for(&server, |server| server.pixel(10, 5) == Some(color)));
+
+    let red = rgb(0xFF, 0, 0);
+    overwrite(&server, 11, 5, red);
+    overwrite(&server, 12, 5, red);
+    assert!(wait_for(&server, |server| server.pixel(12, 5) == Some(color)));
+    assert_eq!(server.pixel(11, 5), Some(red));
+
+    codec.join().unwrap();
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Connects to a server, but fails the write with the number `fail_at`
+/// without sending anything, as if the connection was lost right then
+struct Unreliable {
+    remote: Remote,
+    writes: Arc<AtomicUsize>,
+    fail_at: usize,
+}
+
+struct UnreliableSocket {
+    stream: Stream,
+    writes: Arc<AtomicUsize>,
+    fail_at: usize,
+}
+
+impl Connector for Unreliable {
+    type Socket = UnreliableSocket;
+
+    fn connect(&self) -> std::io::Result<UnreliableSocket> {
+        Ok(UnreliableSocket {
+            stream: self.remote.connect()?,
+            writes: self.writes.clone(),
+            fail_at: self.fail_at,
+        })
+    }
+}
+
+impl Read for UnreliableSocket {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for UnreliableSocket {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.writes.fetch_add(1, Ordering::SeqCst) == self.fail_at {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Socket for UnreliableSocket {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+}
+
+/// Fills the canvas one row per frame, and only sends the new row
+struct Rows {
+    color: Color,
+    y: usize,
+}
+
+impl DataProducer for Rows {
+    fn do_setup(&mut self, _codec: &CodecData) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn get_next_data(&mut self) -> Result<(Frame, Option<Duration>), RunError> {
+        let row: String = (0..WIDTH)
+            .map(|x| format!("PX {} {} {:x}\n", x, self.y, self.color))
+            .collect();
+        self.y += 1;
+
+        let next_data = Some(Duration::from_millis(5)).filter(|_| self.y < HEIGHT);
+        Ok((
+            Frame {
+                chunks: vec![row.into_bytes()],
+                pixels: WIDTH,
+            },
+            next_data,
+        ))
+    }
+
+    fn incremental(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn resends_the_frame_that_failed() {
+    for queue_depth in [0, 2] {
+        let server = start_server();
+        let color = rgb(0x12, 0x34, 0x56);
+
+        let connector = Unreliable {
+            remote: remote(&server),
+            writes: Arc::default(),
+            fail_at: 20,
+        };
+        let options = CodecOptions {
+            reconnect: Some(ReconnectOptions {
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(100),
+            }),
+            queue_depth,
+            queue_policy: QueuePolicy::Block,
+            ..options()
+        };
+        let writes = connector.writes.clone();
+        Codec::new(connector, Rows { color, y: 0 }, options)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(writes.load(Ordering::SeqCst) > 20);
+        assert!(
+            wait_for(&server, |server| is_filled(server, color)),
+            "queue depth {}",
+            queue_depth
+        );
+    }
+}